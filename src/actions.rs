@@ -0,0 +1,344 @@
+use std::{
+    collections::{BTreeMap, HashSet},
+    fs, io,
+    path::{Path, PathBuf},
+};
+
+use bevy::{
+    app::{App, Plugin, PreUpdate, Update},
+    ecs::{
+        change_detection::DetectChanges,
+        schedule::IntoSystemConfigs,
+        system::{Res, ResMut, Resource},
+    },
+    input::{
+        gamepad::{GamepadAxis, GamepadAxisType, GamepadButton, GamepadButtonType},
+        keyboard::KeyCode,
+        mouse::MouseButton,
+        Axis, ButtonInput, InputSystem,
+    },
+    log::{info, warn},
+};
+use serde::{Deserialize, Serialize};
+
+/// Something the player can do, bound to inputs by the `ActionMap`
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Hash, Serialize, Deserialize)]
+pub enum Action {
+    MoveForward,
+    MoveBackward,
+    MoveLeft,
+    MoveRight,
+    /// Fly up
+    MoveUp,
+    /// Fly down
+    MoveDown,
+    Jump,
+    Sprint,
+    Crouch,
+    /// Switch between `CameraMode::Fly` and `CameraMode::Walk`
+    ToggleMode,
+    /// Switch between `CameraMode::Fly`, `CameraMode::Orbit` and `CameraMode::TopDown`
+    CycleCameraMode,
+    /// Grab the cursor for mouse look
+    GrabCursor,
+    /// Free the cursor grabbed for mouse look
+    ReleaseCursor,
+    /// Pause or resume, see `PauseState`
+    Pause,
+    /// Start or stop recording the camera path, see `CameraPathPlugin`
+    RecordCameraPath,
+    /// Start or stop playing the recorded camera path
+    PlayCameraPath,
+    /// Play the recorded camera path and write per frame statistics
+    BenchmarkCameraPath,
+    /// Show or hide the debug overlay, see `DebugOverlayPlugin`
+    ToggleDebugOverlay,
+    /// Show or hide the chunk borders, see `ChunkGizmoPlugin`
+    ToggleChunkBorders,
+    /// Show the octree of the chunk the camera is in, or hide it
+    ToggleChunkOctree,
+    /// Open or close the developer console, see `ConsolePlugin`
+    ToggleConsole,
+}
+
+/// Input triggering an action
+#[derive(Debug, Clone, Copy, PartialEq, Serialize, Deserialize)]
+pub enum Binding {
+    Key(KeyCode),
+    Mouse(MouseButton),
+    /// Button of any connected gamepad
+    GamepadButton(GamepadButtonType),
+    /// Axis of any connected gamepad, pressed past the threshold (below it when the threshold is negative)
+    GamepadAxis(GamepadAxisType, f32),
+}
+
+/// Bindings of every action, an action can have several bindings and is pressed when any of them is
+#[derive(Debug, Clone, PartialEq, Resource, Serialize, Deserialize)]
+#[serde(transparent)]
+pub struct ActionMap {
+    pub bindings: BTreeMap<Action, Vec<Binding>>,
+}
+
+impl Default for ActionMap {
+    fn default() -> Self {
+        return Self::empty()
+            .with_binding(Action::MoveForward, Binding::Key(KeyCode::KeyW))
+            .with_binding(Action::MoveForward, Binding::GamepadAxis(GamepadAxisType::LeftStickY, 0.5))
+            .with_binding(Action::MoveBackward, Binding::Key(KeyCode::KeyS))
+            .with_binding(Action::MoveBackward, Binding::GamepadAxis(GamepadAxisType::LeftStickY, -0.5))
+            .with_binding(Action::MoveLeft, Binding::Key(KeyCode::KeyA))
+            .with_binding(Action::MoveLeft, Binding::GamepadAxis(GamepadAxisType::LeftStickX, -0.5))
+            .with_binding(Action::MoveRight, Binding::Key(KeyCode::KeyD))
+            .with_binding(Action::MoveRight, Binding::GamepadAxis(GamepadAxisType::LeftStickX, 0.5))
+            .with_binding(Action::MoveUp, Binding::Key(KeyCode::Space))
+            .with_binding(Action::MoveUp, Binding::GamepadButton(GamepadButtonType::DPadUp))
+            .with_binding(Action::MoveDown, Binding::Key(KeyCode::ControlLeft))
+            .with_binding(Action::MoveDown, Binding::GamepadButton(GamepadButtonType::DPadDown))
+            .with_binding(Action::Jump, Binding::Key(KeyCode::Space))
+            .with_binding(Action::Jump, Binding::GamepadButton(GamepadButtonType::South))
+            .with_binding(Action::Sprint, Binding::Key(KeyCode::ShiftLeft))
+            .with_binding(Action::Sprint, Binding::GamepadButton(GamepadButtonType::LeftThumb))
+            .with_binding(Action::Crouch, Binding::Key(KeyCode::ControlLeft))
+            .with_binding(Action::Crouch, Binding::GamepadButton(GamepadButtonType::East))
+            .with_binding(Action::ToggleMode, Binding::Key(KeyCode::KeyF))
+            .with_binding(Action::ToggleMode, Binding::GamepadButton(GamepadButtonType::North))
+            .with_binding(Action::CycleCameraMode, Binding::Key(KeyCode::KeyC))
+            .with_binding(Action::CycleCameraMode, Binding::GamepadButton(GamepadButtonType::Select))
+            .with_binding(Action::GrabCursor, Binding::Mouse(MouseButton::Left))
+            .with_binding(Action::ReleaseCursor, Binding::Key(KeyCode::Escape))
+            .with_binding(Action::Pause, Binding::Key(KeyCode::KeyP))
+            .with_binding(Action::Pause, Binding::GamepadButton(GamepadButtonType::Start))
+            .with_binding(Action::RecordCameraPath, Binding::Key(KeyCode::F5))
+            .with_binding(Action::PlayCameraPath, Binding::Key(KeyCode::F6))
+            .with_binding(Action::BenchmarkCameraPath, Binding::Key(KeyCode::F7))
+            .with_binding(Action::ToggleDebugOverlay, Binding::Key(KeyCode::F3))
+            .with_binding(Action::ToggleChunkBorders, Binding::Key(KeyCode::F9))
+            .with_binding(Action::ToggleChunkOctree, Binding::Key(KeyCode::F10))
+            .with_binding(Action::ToggleConsole, Binding::Key(KeyCode::Backquote));
+    }
+}
+
+impl ActionMap {
+    /// Map without any binding
+    pub fn empty() -> Self {
+        Self { bindings: BTreeMap::new() }
+    }
+
+    pub fn with_binding(mut self, action: Action, binding: Binding) -> Self {
+        self.bind(action, binding);
+        return self;
+    }
+
+    /// Add a binding to `action`, keeping its other bindings
+    pub fn bind(&mut self, action: Action, binding: Binding) {
+        let bindings = self.bindings.entry(action).or_default();
+        if !bindings.contains(&binding) {
+            bindings.push(binding);
+        }
+    }
+
+    /// Remove every binding of `action`
+    pub fn unbind(&mut self, action: Action) {
+        self.bindings.remove(&action);
+    }
+
+    pub fn get_bindings(&self, action: Action) -> &[Binding] {
+        return self.bindings.get(&action).map_or(&[], |bindings| bindings.as_slice());
+    }
+
+    /// Read bindings from a RON file
+    pub fn load(path: impl AsRef<Path>) -> io::Result<Self> {
+        let content = fs::read_to_string(path)?;
+        return ron::from_str(&content).map_err(|e| io::Error::new(io::ErrorKind::InvalidData, e));
+    }
+
+    /// Write bindings to a RON file
+    pub fn save(&self, path: impl AsRef<Path>) -> io::Result<()> {
+        let content = ron::ser::to_string_pretty(self, ron::ser::PrettyConfig::default()).map_err(|e| io::Error::new(io::ErrorKind::InvalidData, e))?;
+        return fs::write(path, content);
+    }
+}
+
+/// File the `ActionMap` is saved to whenever it changes
+#[derive(Debug, Clone, Resource)]
+pub struct ActionMapFile(pub PathBuf);
+
+/// Actions pressed this frame, updated from the `ActionMap` before `Update`
+#[derive(Debug, Default, Resource)]
+pub struct ActionState {
+    pressed: HashSet<Action>,
+    previous: HashSet<Action>,
+    /// Set while a UI takes the keyboard (like the console), no action is pressed then
+    pub captured: bool,
+    was_captured: bool,
+    /// Actions whose bindings are pressed, even while captured
+    bound: HashSet<Action>,
+    previous_bound: HashSet<Action>,
+}
+
+impl ActionState {
+    pub fn pressed(&self, action: Action) -> bool {
+        return self.pressed.contains(&action);
+    }
+
+    pub fn just_pressed(&self, action: Action) -> bool {
+        return self.pressed.contains(&action) && !self.previous.contains(&action);
+    }
+
+    pub fn just_released(&self, action: Action) -> bool {
+        return !self.pressed.contains(&action) && self.previous.contains(&action);
+    }
+
+    /// Like `pressed`, but also true while the actions are captured, for the UI capturing them
+    pub fn bound_pressed(&self, action: Action) -> bool {
+        return self.bound.contains(&action);
+    }
+
+    /// Like `just_pressed`, but also true while the actions are captured, for the UI capturing them
+    pub fn bound_just_pressed(&self, action: Action) -> bool {
+        return self.bound.contains(&action) && !self.previous_bound.contains(&action);
+    }
+
+    /// Replace the pressed actions, the current ones become the previous frame's
+    pub fn update(&mut self, pressed: HashSet<Action>) {
+        self.previous = std::mem::replace(&mut self.pressed, pressed);
+    }
+}
+
+/// Insert the `ActionMap` and `ActionState` resources, required by `CameraPlugin` and `WalkPlugin`
+///
+/// Input resources are optional, so actions can be triggered in headless apps by inserting a `ButtonInput` by hand.
+pub struct ActionPlugin {
+    action_map: ActionMap,
+    config_file: Option<PathBuf>,
+}
+
+impl Plugin for ActionPlugin {
+    fn build(&self, app: &mut App) {
+        let action_map = match &self.config_file {
+            Some(path) => match ActionMap::load(path) {
+                Ok(action_map) => action_map,
+                Err(e) => {
+                    info!("Using default bindings, can't read {} : {}", path.display(), e);
+                    self.action_map.clone()
+                }
+            },
+            None => self.action_map.clone(),
+        };
+        app.insert_resource(action_map)
+            .init_resource::<ActionState>()
+            .add_systems(PreUpdate, update_action_state.after(InputSystem))
+            .add_systems(Update, save_action_map);
+        if let Some(path) = &self.config_file {
+            app.insert_resource(ActionMapFile(path.clone()));
+        }
+    }
+}
+
+impl ActionPlugin {
+    pub fn new() -> Self {
+        Self {
+            action_map: ActionMap::default(),
+            config_file: None,
+        }
+    }
+
+    pub fn with_action_map(mut self, action_map: ActionMap) -> Self {
+        self.action_map = action_map;
+        return self;
+    }
+    /// Load the bindings from `path`, keeping the builder ones if it can't be read,
+    /// and save them there whenever they are changed at runtime
+    pub fn with_config_file(mut self, path: impl Into<PathBuf>) -> Self {
+        self.config_file = Some(path.into());
+        return self;
+    }
+}
+
+impl Default for ActionPlugin {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+fn update_action_state(
+    action_map: Res<ActionMap>,
+    mut action_state: ResMut<ActionState>,
+    keys: Option<Res<ButtonInput<KeyCode>>>,
+    mouse_buttons: Option<Res<ButtonInput<MouseButton>>>,
+    gamepad_buttons: Option<Res<ButtonInput<GamepadButton>>>,
+    gamepad_axes: Option<Res<Axis<GamepadAxis>>>,
+) {
+    let is_pressed = |binding: &Binding| -> bool {
+        match *binding {
+            Binding::Key(key) => keys.as_ref().is_some_and(|keys| keys.pressed(key)),
+            Binding::Mouse(button) => mouse_buttons.as_ref().is_some_and(|buttons| buttons.pressed(button)),
+            Binding::GamepadButton(button_type) => gamepad_buttons
+                .as_ref()
+                .is_some_and(|buttons| buttons.get_pressed().any(|button| button.button_type == button_type)),
+            Binding::GamepadAxis(axis_type, threshold) => gamepad_axes.as_ref().is_some_and(|axes| {
+                axes.devices().filter(|axis| axis.axis_type == axis_type).any(|axis| {
+                    let value = axes.get(*axis).unwrap_or(0.0);
+                    if threshold < 0.0 {
+                        value <= threshold
+                    } else {
+                        value >= threshold
+                    }
+                })
+            }),
+        }
+    };
+    let pressed: HashSet<Action> = action_map
+        .bindings
+        .iter()
+        .filter(|(_, bindings)| bindings.iter().any(is_pressed))
+        .map(|(action, _)| *action)
+        .collect();
+    action_state.previous_bound = std::mem::replace(&mut action_state.bound, pressed.clone());
+    if action_state.captured {
+        action_state.update(HashSet::new());
+    } else if action_state.was_captured {
+        // Inputs still held when the capture ends, like the key closing the console, aren't just pressed
+        action_state.previous = pressed.clone();
+        action_state.pressed = pressed;
+    } else {
+        action_state.update(pressed);
+    }
+    action_state.was_captured = action_state.captured;
+}
+
+fn save_action_map(action_map: Res<ActionMap>, action_map_file: Option<Res<ActionMapFile>>) {
+    let Some(action_map_file) = action_map_file else {
+        return;
+    };
+    if action_map.is_changed() && !action_map.is_added() {
+        if let Err(e) = action_map.save(&action_map_file.0) {
+            warn!("Can't save bindings to {} : {}", action_map_file.0.display(), e);
+        }
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use bevy::input::{gamepad::GamepadAxisType, keyboard::KeyCode, mouse::MouseButton};
+
+    use super::{Action, ActionMap, Binding};
+
+    #[test]
+    fn bindings_round_trip_through_ron() {
+        let action_map = ActionMap::default();
+        let content = ron::to_string(&action_map).unwrap();
+        assert_eq!(ron::from_str::<ActionMap>(&content).unwrap(), action_map);
+    }
+
+    #[test]
+    fn parses_several_bindings_per_action() {
+        let action_map: ActionMap = ron::from_str("{ MoveForward: [Key(ArrowUp), GamepadAxis(LeftStickY, 0.3)], GrabCursor: [Mouse(Left)] }").unwrap();
+        assert_eq!(
+            action_map.get_bindings(Action::MoveForward),
+            &[Binding::Key(KeyCode::ArrowUp), Binding::GamepadAxis(GamepadAxisType::LeftStickY, 0.3)]
+        );
+        assert_eq!(action_map.get_bindings(Action::GrabCursor), &[Binding::Mouse(MouseButton::Left)]);
+        assert!(action_map.get_bindings(Action::Jump).is_empty());
+    }
+}
@@ -0,0 +1,52 @@
+//! Export the chunks of a world saved by the demo `save` console command to an OBJ or binary glTF file, without opening a window
+//!
+//! Usage : `export <world.bin> <output.obj|output.glb> [min_x min_z max_x max_z]`
+
+use std::{env, process::ExitCode};
+
+use bevy::math::IVec2;
+use voxel_engine::prelude::*;
+
+fn main() -> ExitCode {
+    let args = env::args().skip(1).collect::<Vec<_>>();
+    if args.len() != 2 && args.len() != 6 {
+        eprintln!("Usage : export <world.bin> <output.obj|output.glb> [min_x min_z max_x max_z]");
+        return ExitCode::FAILURE;
+    }
+    let world = match VoxelWorld::<BasicSet>::load_chunks(&args[0]) {
+        Ok(world) => world,
+        Err(e) => {
+            eprintln!("Can't read {} : {}", args[0], e);
+            return ExitCode::FAILURE;
+        }
+    };
+
+    let (min, max) = if args.len() == 6 {
+        let coords = match args[2..].iter().map(|arg| arg.parse::<i32>()).collect::<Result<Vec<_>, _>>() {
+            Ok(coords) => coords,
+            Err(e) => {
+                eprintln!("Invalid chunk coordinates : {}", e);
+                return ExitCode::FAILURE;
+            }
+        };
+        (IVec2::new(coords[0], coords[1]), IVec2::new(coords[2], coords[3]))
+    } else {
+        // Every loaded chunk
+        let positions = world.chunk_positions();
+        (
+            positions.iter().copied().reduce(IVec2::min).unwrap_or(IVec2::ZERO),
+            positions.iter().copied().reduce(IVec2::max).unwrap_or(IVec2::ZERO),
+        )
+    };
+
+    match world.export_chunks(min, max, &args[1]) {
+        Ok(count) => {
+            println!("{} chunks exported to {}", count, args[1]);
+            return ExitCode::SUCCESS;
+        }
+        Err(e) => {
+            eprintln!("Can't export to {} : {}", args[1], e);
+            return ExitCode::FAILURE;
+        }
+    }
+}
@@ -1,35 +1,185 @@
-use std::f32::consts::{FRAC_PI_2, PI};
+use std::{
+    f32::consts::{FRAC_PI_2, FRAC_PI_4, PI},
+    fs, io,
+    path::{Path, PathBuf},
+};
 
 use bevy::{
     app::{Plugin, Startup, Update},
     core_pipeline::core_3d::Camera3dBundle,
     ecs::{
+        change_detection::DetectChanges,
         component::Component,
         event::EventReader,
-        system::{Commands, Query, Res},
+        query::With,
+        schedule::{common_conditions::in_state, IntoSystemConfigs, NextState, OnEnter, State, States},
+        system::{Commands, Query, Res, ResMut, Resource},
+        world::Ref,
     },
-    input::{keyboard::KeyCode, mouse::MouseMotion, ButtonInput},
-    log::info,
-    math::Vec3,
+    input::mouse::{MouseMotion, MouseScrollUnit, MouseWheel},
+    log::{info, warn},
+    math::{EulerRot, Quat, Vec2, Vec3},
+    time::Time,
     transform::components::Transform,
+    window::{CursorGrabMode, PrimaryWindow, Window, WindowFocused},
+};
+use serde::{Deserialize, Serialize};
+
+use crate::{
+    actions::{Action, ActionState},
+    chunk::WIDTH,
 };
 
+/// Highest angle of an orbiting camera above or below its target
+const MAX_ORBIT_PITCH: f32 = FRAC_PI_2 - 0.01;
+/// Mouse wheel pixels counted as one line on touchpads
+const PIXELS_PER_SCROLL_LINE: f32 = 100.0;
+
 #[derive(Debug, Component)]
-pub struct CameraId(u32);
+pub struct CameraId(pub u32);
+
+/// How the camera is moved, can be switched at runtime
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Component)]
+pub enum CameraMode {
+    /// Free flight ignoring the world
+    Fly,
+    /// Walking with gravity and collisions, see `WalkPlugin`
+    Walk,
+    /// Turning around a target point, zoomed with the mouse wheel
+    Orbit,
+    /// Looking down on the map, moved chunk by chunk
+    TopDown,
+}
+
+impl CameraMode {
+    /// Mode selected by `Action::CycleCameraMode`
+    pub fn next(&self) -> Self {
+        return match self {
+            CameraMode::Fly | CameraMode::Walk => CameraMode::Orbit,
+            CameraMode::Orbit => CameraMode::TopDown,
+            CameraMode::TopDown => CameraMode::Fly,
+        };
+    }
+}
+
+/// `CameraId` of the camera receiving input, the first `CameraPlugin` added by default
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Resource)]
+pub struct ControlledCamera(pub u32);
+
+/// Whether the cursor is locked to the window for mouse look, it is grabbed by clicking in the window
+/// and released with `Action::ReleaseCursor`, when the window loses focus or when the game is paused
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, Default, States)]
+pub enum CursorState {
+    #[default]
+    Released,
+    Grabbed,
+}
+
+/// Toggled with `Action::Pause`, camera input is suspended while paused
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, Default, States)]
+pub enum PauseState {
+    #[default]
+    Running,
+    Paused,
+}
+
+/// Settings of a camera spawned by `CameraPlugin`, read every frame so they can be changed at runtime
+#[derive(Debug, Clone, PartialEq, Component, Serialize, Deserialize)]
+#[serde(default)]
+pub struct CameraSettings {
+    /// Voxels per second
+    pub translation_speed: f32,
+    /// Radians per pixel of mouse movement
+    pub rotation_speed: f32,
+    /// Voxels per second squared to reach `translation_speed`, 0 to reach it instantly
+    pub acceleration: f32,
+    /// Rate at which the camera slows down when no key is pressed, 0 to stop instantly
+    pub damping: f32,
+    /// Time constant of the mouse look smoothing in seconds, 0 to disable it
+    pub mouse_smoothing: f32,
+    /// Distance to the target when entering `CameraMode::Orbit`
+    pub orbit_distance: f32,
+    /// Part of the distance zoomed per mouse wheel step
+    pub zoom_speed: f32,
+    /// Height of the camera when entering `CameraMode::TopDown`
+    pub top_down_height: f32,
+}
 
+impl Default for CameraSettings {
+    fn default() -> Self {
+        Self {
+            translation_speed: 10.0,
+            rotation_speed: 1.0,
+            acceleration: 0.0,
+            damping: 0.0,
+            mouse_smoothing: 0.0,
+            orbit_distance: 16.0,
+            zoom_speed: 0.1,
+            top_down_height: 96.0,
+        }
+    }
+}
+
+impl CameraSettings {
+    /// Read settings from a RON file, missing fields keep their default value
+    pub fn load(path: impl AsRef<Path>) -> io::Result<Self> {
+        let content = fs::read_to_string(path)?;
+        return ron::from_str(&content).map_err(|e| io::Error::new(io::ErrorKind::InvalidData, e));
+    }
+
+    /// Write settings to a RON file
+    pub fn save(&self, path: impl AsRef<Path>) -> io::Result<()> {
+        let content = ron::ser::to_string_pretty(self, ron::ser::PrettyConfig::default()).map_err(|e| io::Error::new(io::ErrorKind::InvalidData, e))?;
+        return fs::write(path, content);
+    }
+}
+
+/// File the `CameraSettings` of a camera are saved to whenever they change
+#[derive(Debug, Clone, Component)]
+pub struct CameraSettingsFile(pub PathBuf);
+
+/// Movement state of a flying camera
+#[derive(Debug, Default, Component)]
+pub struct FlyState {
+    pub velocity: Vec3,
+    /// Mouse movement not applied yet because of smoothing
+    pub pending_mouse_delta: Vec2,
+}
+
+/// Target and orientation of a camera in `CameraMode::Orbit` or `CameraMode::TopDown`
+#[derive(Debug, Default, Component)]
+pub struct SpectatorState {
+    pub target: Vec3,
+    pub distance: f32,
+    pub yaw: f32,
+    pub pitch: f32,
+}
+
+impl SpectatorState {
+    fn rotation(&self) -> Quat {
+        return Quat::from_euler(EulerRot::YXZ, self.yaw, self.pitch, 0.0);
+    }
+
+    /// Camera transform looking at the target from `distance`
+    pub fn transform(&self) -> Transform {
+        let rotation = self.rotation();
+        return Transform::from_translation(self.target + rotation * Vec3::Z * self.distance).with_rotation(rotation);
+    }
+
+    /// Horizontal direction the camera faces
+    fn forward(&self) -> Vec3 {
+        return Quat::from_rotation_y(self.yaw) * Vec3::NEG_Z;
+    }
+}
+
+/// Spawn a camera with `CameraSettings`, `CameraMode` and `FlyState` components, moved by the actions of `ActionPlugin`
 pub struct CameraPlugin {
     x: f32,
     y: f32,
     z: f32,
     id: u32,
-    translation_speed: f32,
-    rotation_speed: f32,
-    forward: KeyCode,
-    backward: KeyCode,
-    left: KeyCode,
-    right: KeyCode,
-    up: KeyCode,
-    down: KeyCode,
+    settings: CameraSettings,
+    settings_file: Option<PathBuf>,
 }
 
 impl Plugin for CameraPlugin {
@@ -38,100 +188,55 @@ impl Plugin for CameraPlugin {
         let y = self.y;
         let z = self.z;
         let id: u32 = self.id;
-        let forward_key = self.forward;
-        let backward_key = self.backward;
-        let left_key = self.left;
-        let right_key = self.right;
-        let up_key = self.up;
-        let down_key = self.down;
-        let translation_speed = self.translation_speed;
-        let rotation_speed = self.rotation_speed;
+        let settings = self.settings.clone();
+        let settings_file = self.settings_file.clone();
         app.add_systems(Startup, move |mut commands: Commands| {
-            commands
-                .spawn(Camera3dBundle {
-                    transform: Transform::from_xyz(x, y, z),
-                    ..Default::default()
-                })
-                .insert(CameraId(id));
-        })
-        .add_systems(
-            Update,
-            move |mut cameras: Query<(&mut Transform, &CameraId)>,
-                  inputs: Res<ButtonInput<KeyCode>>,
-                  mut mouse_motion_events: EventReader<MouseMotion>| {
-                for (mut camera_transform, camera_id) in cameras.iter_mut() {
-                    if camera_id.0 == id {
-                        // log::info!("Current pos : {}, {}, {}", camera_transform.translation.x, camera_transform.translation.y, camera_transform.translation.z);
-                        // info!("Rotation around y : {}", rotate_around_y);
-                        if inputs.pressed(forward_key) {
-                            let mut forward = camera_transform.forward().normalize();
-                            forward.y = 0.0;
-                            forward = forward.normalize();
-                            camera_transform.translation += forward * translation_speed;
-                        }
-                        if inputs.pressed(backward_key) {
-                            let mut backward = camera_transform.back().normalize();
-                            backward.y = 0.0;
-                            backward = backward.normalize();
-                            camera_transform.translation += backward * translation_speed;
-                        }
-                        if inputs.pressed(left_key) {
-                            let left = camera_transform.left();
-                            camera_transform.translation += left * translation_speed;
-                        }
-                        if inputs.pressed(right_key) {
-                            let right = camera_transform.right();
-                            camera_transform.translation += right * translation_speed;
-                        }
-                        if inputs.pressed(up_key) {
-                            // let up = camera_transform.up();
-                            camera_transform.translation +=
-                                Vec3::new(0.0, 1.0, 0.0) * translation_speed;
-                        }
-                        if inputs.pressed(down_key) {
-                            // let down = camera_transform.down();
-                            camera_transform.translation +=
-                                Vec3::new(0.0, -1.0, 0.0) * translation_speed;
-                        }
-
-                        for event in mouse_motion_events.read() {
-                            // info!("Mouse event : {:?}", event);
-                            // info!(
-                            //     "Euler angle : {} * PI",
-                            //     camera_transform
-                            //         .rotation
-                            //         .to_euler(bevy::math::EulerRot::YXZ)
-                            //         .1
-                            //         / PI
-                            // );
-                            // info!("Add : {}", event.delta.x * rotation_speed);
-                            // info!("Axis, angle : {}, {}", camera_transform.rotation.to_axis_angle().0, camera_transform.rotation.to_axis_angle().1);
-                            // Change detection mechanism, it doesn't works
-                            if (camera_transform
-                                .rotation
-                                .to_euler(bevy::math::EulerRot::YXZ)
-                                .1
-                                - event.delta.y * rotation_speed)
-                                .abs()
-                                < FRAC_PI_2
-                            {
-                                // info!(
-                                //     "Test passed, angle : {}, PI/2 : {}",
-                                //     camera_transform
-                                //         .rotation
-                                //         .to_euler(bevy::math::EulerRot::YXZ)
-                                //         .1,
-                                //     FRAC_PI_2
-                                // );
-                                camera_transform.rotate_local_x(-event.delta.y * rotation_speed);
-                            }
-                            camera_transform.rotate_y(-event.delta.x * rotation_speed);
+            let mut camera = commands.spawn(Camera3dBundle {
+                transform: Transform::from_xyz(x, y, z),
+                ..Default::default()
+            });
+            camera.insert((CameraId(id), CameraMode::Fly, FlyState::default(), SpectatorState::default()));
+            match &settings_file {
+                Some(path) => {
+                    let settings = match CameraSettings::load(path) {
+                        Ok(settings) => settings,
+                        Err(e) => {
+                            info!("Using default camera settings, can't read {} : {}", path.display(), e);
+                            settings.clone()
                         }
-                        break;
-                    }
+                    };
+                    camera.insert((settings, CameraSettingsFile(path.clone())));
+                }
+                None => {
+                    camera.insert(settings.clone());
                 }
-            },
-        );
+            }
+        });
+
+        // Shared by every camera, only added by the first plugin
+        if app.world.contains_resource::<ControlledCamera>() {
+            return;
+        }
+        app.insert_resource(ControlledCamera(id))
+            .init_state::<CursorState>()
+            .init_state::<PauseState>()
+            .add_systems(OnEnter(CursorState::Grabbed), apply_cursor_grab(true))
+            .add_systems(OnEnter(CursorState::Released), apply_cursor_grab(false))
+            .add_systems(OnEnter(PauseState::Paused), release_cursor)
+            .add_systems(
+                Update,
+                (
+                    (toggle_pause, update_cursor_state),
+                    (cycle_camera_mode, fly_camera, spectator_camera).chain().run_if(in_state(PauseState::Running)),
+                    save_camera_settings,
+                )
+                    .chain(),
+            );
+    }
+
+    /// One plugin is added per camera
+    fn is_unique(&self) -> bool {
+        false
     }
 }
 
@@ -142,47 +247,411 @@ impl CameraPlugin {
             y: y,
             z: z,
             id: id,
-            translation_speed: 1.0,
-            rotation_speed: 1.0,
-            forward: KeyCode::KeyW,
-            backward: KeyCode::KeyS,
-            left: KeyCode::KeyA,
-            right: KeyCode::KeyD,
-            up: KeyCode::Space,
-            down: KeyCode::ControlLeft,
+            settings: CameraSettings::default(),
+            settings_file: None,
         }
     }
 
-    pub fn with_forward_key(mut self, key: KeyCode) -> Self {
-        self.forward = key;
+    pub fn with_settings(mut self, settings: CameraSettings) -> Self {
+        self.settings = settings;
         return self;
     }
-    pub fn with_backward_key(mut self, key: KeyCode) -> Self {
-        self.backward = key;
+    /// Load the settings from `path` at startup, keeping the builder values if it can't be read,
+    /// and save them there whenever they are changed at runtime
+    pub fn with_settings_file(mut self, path: impl Into<PathBuf>) -> Self {
+        self.settings_file = Some(path.into());
         return self;
     }
-    pub fn with_left_key(mut self, key: KeyCode) -> Self {
-        self.left = key;
+    pub fn with_translation_speed(mut self, translation_speed: f32) -> Self {
+        self.settings.translation_speed = translation_speed;
         return self;
     }
-    pub fn with_right_key(mut self, key: KeyCode) -> Self {
-        self.right = key;
+    pub fn with_rotation_speed(mut self, rotation_speed: f32) -> Self {
+        self.settings.rotation_speed = rotation_speed;
         return self;
     }
-    pub fn with_up_key(mut self, key: KeyCode) -> Self {
-        self.up = key;
+    pub fn with_acceleration(mut self, acceleration: f32) -> Self {
+        self.settings.acceleration = acceleration;
         return self;
     }
-    pub fn with_down_key(mut self, key: KeyCode) -> Self {
-        self.down = key;
+    pub fn with_damping(mut self, damping: f32) -> Self {
+        self.settings.damping = damping;
         return self;
     }
-    pub fn with_translation_speed(mut self, translation_speed: f32) -> Self {
-        self.translation_speed = translation_speed;
+    pub fn with_mouse_smoothing(mut self, mouse_smoothing: f32) -> Self {
+        self.settings.mouse_smoothing = mouse_smoothing;
         return self;
     }
-    pub fn with_rotation_speed(mut self, rotation_speed: f32) -> Self {
-        self.rotation_speed = rotation_speed;
-        return self;
+}
+
+fn cycle_camera_mode(
+    actions: Res<ActionState>,
+    controlled_camera: Res<ControlledCamera>,
+    mut cameras: Query<(&CameraId, &mut CameraMode, &CameraSettings, &mut Transform, &mut SpectatorState)>,
+) {
+    if !actions.just_pressed(Action::CycleCameraMode) {
+        return;
+    }
+    for (camera_id, mut camera_mode, settings, mut camera_transform, mut spectator) in cameras.iter_mut() {
+        if camera_id.0 != controlled_camera.0 {
+            continue;
+        }
+        let (yaw, pitch, _) = camera_transform.rotation.to_euler(EulerRot::YXZ);
+        *camera_mode = camera_mode.next();
+        match *camera_mode {
+            CameraMode::Orbit => {
+                *spectator = SpectatorState {
+                    target: camera_transform.translation + *camera_transform.forward() * settings.orbit_distance,
+                    distance: settings.orbit_distance,
+                    yaw: yaw,
+                    pitch: pitch.clamp(-MAX_ORBIT_PITCH, MAX_ORBIT_PITCH),
+                };
+                *camera_transform = spectator.transform();
+            }
+            CameraMode::TopDown => {
+                let target = spectator.target;
+                *spectator = SpectatorState {
+                    target: Vec3::new(snap_to_chunk_center(target.x), 0.0, snap_to_chunk_center(target.z)),
+                    distance: settings.top_down_height,
+                    yaw: yaw,
+                    pitch: -FRAC_PI_2,
+                };
+                *camera_transform = spectator.transform();
+            }
+            _ => {
+                // Back to a horizontal look so that moving forward doesn't dive into the ground
+                camera_transform.rotation = Quat::from_euler(EulerRot::YXZ, spectator.yaw, spectator.pitch.max(-FRAC_PI_4), 0.0);
+            }
+        }
+    }
+}
+
+/// Move the cameras in `CameraMode::Orbit` and `CameraMode::TopDown`
+fn spectator_camera(
+    actions: Res<ActionState>,
+    time: Res<Time>,
+    controlled_camera: Res<ControlledCamera>,
+    cursor_state: Res<State<CursorState>>,
+    mut mouse_motion_events: EventReader<MouseMotion>,
+    mut mouse_wheel_events: EventReader<MouseWheel>,
+    mut cameras: Query<(&CameraId, &CameraMode, &CameraSettings, &mut Transform, &mut SpectatorState)>,
+) {
+    let mouse_motion: Vec2 = mouse_motion_events.read().map(|event| event.delta).sum();
+    // The mouse is ignored while a UI like the console captures the actions
+    let mouse_look = *cursor_state.get() == CursorState::Grabbed && !actions.captured;
+    let scroll: f32 = mouse_wheel_events
+        .read()
+        .map(|event| match event.unit {
+            MouseScrollUnit::Line => event.y,
+            MouseScrollUnit::Pixel => event.y / PIXELS_PER_SCROLL_LINE,
+        })
+        .sum();
+    for (camera_id, camera_mode, settings, mut camera_transform, mut spectator) in cameras.iter_mut() {
+        if camera_id.0 != controlled_camera.0 || !matches!(camera_mode, CameraMode::Orbit | CameraMode::TopDown) {
+            continue;
+        }
+        if !actions.captured {
+            spectator.distance = (spectator.distance * (1.0 - scroll * settings.zoom_speed)).max(1.0);
+        }
+
+        let forward = spectator.forward();
+        let right = Vec3::new(-forward.z, 0.0, forward.x);
+        if *camera_mode == CameraMode::Orbit {
+            if mouse_look {
+                spectator.yaw -= mouse_motion.x * settings.rotation_speed;
+                spectator.pitch = (spectator.pitch - mouse_motion.y * settings.rotation_speed).clamp(-MAX_ORBIT_PITCH, MAX_ORBIT_PITCH);
+            }
+            // The target is moved like a flying camera
+            let mut direction = Vec3::ZERO;
+            for (action, action_direction) in [
+                (Action::MoveForward, forward),
+                (Action::MoveBackward, -forward),
+                (Action::MoveRight, right),
+                (Action::MoveLeft, -right),
+                (Action::MoveUp, Vec3::Y),
+                (Action::MoveDown, Vec3::NEG_Y),
+            ] {
+                if actions.pressed(action) {
+                    direction += action_direction;
+                }
+            }
+            spectator.target += direction * settings.translation_speed * time.delta_seconds();
+        } else {
+            for (action, action_direction) in [
+                (Action::MoveForward, forward),
+                (Action::MoveBackward, -forward),
+                (Action::MoveRight, right),
+                (Action::MoveLeft, -right),
+            ] {
+                if actions.just_pressed(action) {
+                    let target = spectator.target + action_direction * WIDTH as f32;
+                    spectator.target = Vec3::new(snap_to_chunk_center(target.x), 0.0, snap_to_chunk_center(target.z));
+                }
+            }
+        }
+        *camera_transform = spectator.transform();
+    }
+}
+
+/// Return the center of the chunk containing the coordinate `x`
+fn snap_to_chunk_center(x: f32) -> f32 {
+    return ((x / WIDTH as f32).floor() + 0.5) * WIDTH as f32;
+}
+
+fn fly_camera(
+    controlled_camera: Res<ControlledCamera>,
+    mut cameras: Query<(&CameraId, &mut Transform, &CameraMode, &CameraSettings, &mut FlyState)>,
+    actions: Res<ActionState>,
+    time: Res<Time>,
+    cursor_state: Res<State<CursorState>>,
+    mut mouse_motion_events: EventReader<MouseMotion>,
+) {
+    let delta = time.delta_seconds();
+    let mouse_motion: Vec2 = mouse_motion_events.read().map(|event| event.delta).sum();
+    // Mouse movements are still read to drop them while the cursor is free or the actions are captured
+    let mouse_look = *cursor_state.get() == CursorState::Grabbed && !actions.captured;
+    for (camera_id, mut camera_transform, camera_mode, settings, mut state) in cameras.iter_mut() {
+        if camera_id.0 != controlled_camera.0 {
+            continue;
+        }
+        if !matches!(camera_mode, CameraMode::Fly | CameraMode::Walk) {
+            *state = FlyState::default();
+            continue;
+        }
+        // log::info!("Current pos : {}, {}, {}", camera_transform.translation.x, camera_transform.translation.y, camera_transform.translation.z);
+        // info!("Rotation around y : {}", rotate_around_y);
+        // Walking translation is handled by `WalkPlugin`, only mouse look is shared
+        if *camera_mode == CameraMode::Fly {
+            let mut direction = Vec3::ZERO;
+            if actions.pressed(Action::MoveForward) {
+                let mut forward = camera_transform.forward().normalize();
+                forward.y = 0.0;
+                direction += forward.normalize();
+            }
+            if actions.pressed(Action::MoveBackward) {
+                let mut backward = camera_transform.back().normalize();
+                backward.y = 0.0;
+                direction += backward.normalize();
+            }
+            if actions.pressed(Action::MoveLeft) {
+                direction += *camera_transform.left();
+            }
+            if actions.pressed(Action::MoveRight) {
+                direction += *camera_transform.right();
+            }
+            if actions.pressed(Action::MoveUp) {
+                direction += Vec3::new(0.0, 1.0, 0.0);
+            }
+            if actions.pressed(Action::MoveDown) {
+                direction += Vec3::new(0.0, -1.0, 0.0);
+            }
+
+            let target_velocity = direction * settings.translation_speed;
+            if direction != Vec3::ZERO {
+                state.velocity = if settings.acceleration > 0.0 {
+                    move_towards(state.velocity, target_velocity, settings.acceleration * delta)
+                } else {
+                    target_velocity
+                };
+            } else if settings.damping > 0.0 {
+                state.velocity *= (-settings.damping * delta).exp();
+            } else {
+                state.velocity = Vec3::ZERO;
+            }
+            camera_transform.translation += state.velocity * delta;
+        } else {
+            state.velocity = Vec3::ZERO;
+        }
+
+        // info!("Mouse motion : {:?}", mouse_motion);
+        if mouse_look {
+            state.pending_mouse_delta += mouse_motion;
+        } else {
+            state.pending_mouse_delta = Vec2::ZERO;
+        }
+        // Apply part of the mouse movement each frame, the rest is kept for the next ones
+        let mouse_delta = if settings.mouse_smoothing > 0.0 {
+            state.pending_mouse_delta * (1.0 - (-delta / settings.mouse_smoothing).exp())
+        } else {
+            state.pending_mouse_delta
+        };
+        state.pending_mouse_delta -= mouse_delta;
+
+        // info!(
+        //     "Euler angle : {} * PI",
+        //     camera_transform
+        //         .rotation
+        //         .to_euler(bevy::math::EulerRot::YXZ)
+        //         .1
+        //         / PI
+        // );
+        // info!("Add : {}", mouse_delta.x * settings.rotation_speed);
+        // info!("Axis, angle : {}, {}", camera_transform.rotation.to_axis_angle().0, camera_transform.rotation.to_axis_angle().1);
+        // Change detection mechanism, it doesn't works
+        if (camera_transform
+            .rotation
+            .to_euler(bevy::math::EulerRot::YXZ)
+            .1
+            - mouse_delta.y * settings.rotation_speed)
+            .abs()
+            < FRAC_PI_2
+        {
+            // info!(
+            //     "Test passed, angle : {}, PI/2 : {}",
+            //     camera_transform
+            //         .rotation
+            //         .to_euler(bevy::math::EulerRot::YXZ)
+            //         .1,
+            //     FRAC_PI_2
+            // );
+            camera_transform.rotate_local_x(-mouse_delta.y * settings.rotation_speed);
+        }
+        camera_transform.rotate_y(-mouse_delta.x * settings.rotation_speed);
+    }
+}
+
+fn toggle_pause(actions: Res<ActionState>, pause_state: Res<State<PauseState>>, mut next_pause_state: ResMut<NextState<PauseState>>) {
+    if actions.just_pressed(Action::Pause) {
+        next_pause_state.set(match pause_state.get() {
+            PauseState::Running => PauseState::Paused,
+            PauseState::Paused => PauseState::Running,
+        });
+    }
+}
+
+/// Grab the cursor when clicking in the window and release it on `Action::ReleaseCursor` or when the window loses focus,
+/// clicks are ignored while the actions are captured
+fn update_cursor_state(
+    actions: Res<ActionState>,
+    mut window_focused_events: EventReader<WindowFocused>,
+    cursor_state: Res<State<CursorState>>,
+    pause_state: Res<State<PauseState>>,
+    mut next_cursor_state: ResMut<NextState<CursorState>>,
+) {
+    let focus_lost = window_focused_events.read().any(|event| !event.focused);
+    match cursor_state.get() {
+        CursorState::Grabbed if focus_lost || actions.just_pressed(Action::ReleaseCursor) => next_cursor_state.set(CursorState::Released),
+        CursorState::Released if *pause_state.get() == PauseState::Running && !actions.captured && actions.just_pressed(Action::GrabCursor) => {
+            next_cursor_state.set(CursorState::Grabbed)
+        }
+        _ => {}
+    }
+}
+
+fn release_cursor(mut next_cursor_state: ResMut<NextState<CursorState>>) {
+    next_cursor_state.set(CursorState::Released);
+}
+
+/// Return a system locking and hiding the cursor of the primary window, or freeing it
+fn apply_cursor_grab(grab: bool) -> impl Fn(Query<&mut Window, With<PrimaryWindow>>) {
+    return move |mut windows: Query<&mut Window, With<PrimaryWindow>>| {
+        for mut window in windows.iter_mut() {
+            // Bevy falls back to `Confined` on platforms that can't lock the cursor
+            window.cursor.grab_mode = if grab { CursorGrabMode::Locked } else { CursorGrabMode::None };
+            window.cursor.visible = !grab;
+        }
+    };
+}
+
+/// Save the settings of the cameras with a `CameraSettingsFile` when they are changed
+fn save_camera_settings(cameras: Query<(Ref<CameraSettings>, &CameraSettingsFile)>) {
+    for (settings, settings_file) in cameras.iter() {
+        if settings.is_changed() && !settings.is_added() {
+            if let Err(e) = settings.save(&settings_file.0) {
+                warn!("Can't save camera settings to {} : {}", settings_file.0.display(), e);
+            }
+        }
+    }
+}
+
+/// Return the camera the world follows (LOD, chunk visibility, gizmos) among `cameras`: the `ControlledCamera`,
+/// or the first one in apps without `CameraPlugin`
+pub fn find_controlled_camera<'a, C>(
+    controlled_camera: Option<&ControlledCamera>,
+    mut cameras: impl Iterator<Item = (C, Option<&'a CameraId>)>,
+) -> Option<C> {
+    return match controlled_camera {
+        Some(controlled_camera) => cameras
+            .find(|(_, camera_id)| camera_id.is_some_and(|camera_id| camera_id.0 == controlled_camera.0))
+            .map(|(camera, _)| camera),
+        None => cameras.next().map(|(camera, _)| camera),
+    };
+}
+
+/// Move `from` toward `to` by at most `max_distance`
+fn move_towards(from: Vec3, to: Vec3, max_distance: f32) -> Vec3 {
+    let offset = to - from;
+    let distance = offset.length();
+    if distance <= max_distance {
+        return to;
+    }
+    return from + offset / distance * max_distance;
+}
+
+#[cfg(test)]
+mod test {
+    use std::f32::consts::FRAC_PI_2;
+
+    use bevy::math::Vec3;
+
+    use super::{find_controlled_camera, snap_to_chunk_center, CameraId, CameraSettings, ControlledCamera, SpectatorState};
+
+    #[test]
+    fn settings_round_trip_through_file() {
+        let path = std::env::temp_dir().join(format!("camera_settings_{}.ron", std::process::id()));
+        let settings = CameraSettings {
+            translation_speed: 25.0,
+            mouse_smoothing: 0.05,
+            ..Default::default()
+        };
+        settings.save(&path).unwrap();
+        assert_eq!(CameraSettings::load(&path).unwrap(), settings);
+        std::fs::remove_file(&path).unwrap();
+    }
+
+    #[test]
+    fn missing_fields_keep_default_values() {
+        let settings: CameraSettings = ron::from_str("(damping: 3.0)").unwrap();
+        assert_eq!(settings.damping, 3.0);
+        assert_eq!(settings.translation_speed, 10.0);
+    }
+
+    #[test]
+    fn snaps_to_chunk_centers() {
+        assert_eq!(snap_to_chunk_center(3.0), 8.0);
+        assert_eq!(snap_to_chunk_center(16.0), 24.0);
+        assert_eq!(snap_to_chunk_center(-0.5), -8.0);
+    }
+
+    #[test]
+    fn spectator_looks_at_target() {
+        let top_down = SpectatorState {
+            target: Vec3::new(8.0, 0.0, 8.0),
+            distance: 50.0,
+            yaw: 0.3,
+            pitch: -FRAC_PI_2,
+        };
+        let transform = top_down.transform();
+        assert!(transform.translation.distance(Vec3::new(8.0, 50.0, 8.0)) < 1e-4);
+        assert!(transform.forward().distance(Vec3::NEG_Y) < 1e-4);
+
+        let orbit = SpectatorState {
+            target: Vec3::new(1.0, 2.0, 3.0),
+            distance: 10.0,
+            yaw: 1.0,
+            pitch: -0.5,
+        };
+        let transform = orbit.transform();
+        assert!((transform.translation.distance(orbit.target) - 10.0).abs() < 1e-4);
+        assert!((transform.translation + *transform.forward() * 10.0).distance(orbit.target) < 1e-4);
+    }
+
+    #[test]
+    fn finds_the_controlled_camera() {
+        let (first, second) = (CameraId(0), CameraId(1));
+        let cameras = || [("untracked", None), ("first", Some(&first)), ("second", Some(&second))].into_iter();
+        assert_eq!(find_controlled_camera(Some(&ControlledCamera(1)), cameras()), Some("second"));
+        assert_eq!(find_controlled_camera(Some(&ControlledCamera(2)), cameras()), None);
+        assert_eq!(find_controlled_camera(None, cameras()), Some("untracked"));
     }
 }
@@ -0,0 +1,395 @@
+use std::{
+    fmt::Write as _,
+    fs, io,
+    marker::PhantomData,
+    path::{Path, PathBuf},
+};
+
+use bevy::{
+    app::{App, AppExit, Plugin, PostUpdate},
+    asset::{Assets, Handle},
+    ecs::{
+        event::EventWriter,
+        schedule::IntoSystemConfigs,
+        system::{Query, Res, ResMut, Resource},
+    },
+    log::{info, warn},
+    math::{Quat, Vec3},
+    render::{
+        mesh::Mesh,
+        view::{ViewVisibility, VisibilitySystems},
+    },
+    time::Time,
+    transform::{components::Transform, TransformSystem},
+};
+use serde::{Deserialize, Serialize};
+
+use crate::{
+    actions::{Action, ActionState},
+    camera::CameraId,
+    voxel::VoxelSet,
+    world::VoxelWorld,
+};
+
+/// Camera position at `time` seconds from the start of a path
+#[derive(Debug, Clone, Copy, PartialEq, Serialize, Deserialize)]
+pub struct CameraKeyframe {
+    pub time: f32,
+    pub translation: Vec3,
+    pub rotation: Quat,
+}
+
+/// Recorded camera movement, keyframes are sorted by time
+#[derive(Debug, Clone, Default, PartialEq, Serialize, Deserialize)]
+pub struct CameraPath {
+    pub keyframes: Vec<CameraKeyframe>,
+}
+
+impl CameraPath {
+    pub fn duration(&self) -> f32 {
+        return self.keyframes.last().map_or(0.0, |keyframe| keyframe.time);
+    }
+
+    /// Return the camera transform at `time`, interpolated between the surrounding keyframes
+    pub fn sample(&self, time: f32) -> Option<Transform> {
+        let first = self.keyframes.first()?;
+        let next_index = self.keyframes.partition_point(|keyframe| keyframe.time <= time);
+        let (from, to) = if next_index == 0 {
+            (first, first)
+        } else if next_index == self.keyframes.len() {
+            (&self.keyframes[next_index - 1], &self.keyframes[next_index - 1])
+        } else {
+            (&self.keyframes[next_index - 1], &self.keyframes[next_index])
+        };
+        let t = if to.time > from.time { ((time - from.time) / (to.time - from.time)).clamp(0.0, 1.0) } else { 0.0 };
+        return Some(Transform::from_translation(from.translation.lerp(to.translation, t)).with_rotation(from.rotation.slerp(to.rotation, t)));
+    }
+
+    /// Read a path from a RON file
+    pub fn load(path: impl AsRef<Path>) -> io::Result<Self> {
+        let content = fs::read_to_string(path)?;
+        return ron::from_str(&content).map_err(|e| io::Error::new(io::ErrorKind::InvalidData, e));
+    }
+
+    /// Write the path to a RON file
+    pub fn save(&self, path: impl AsRef<Path>) -> io::Result<()> {
+        let content = ron::ser::to_string_pretty(self, ron::ser::PrettyConfig::default()).map_err(|e| io::Error::new(io::ErrorKind::InvalidData, e))?;
+        return fs::write(path, content);
+    }
+}
+
+/// Statistics of one frame of a benchmark
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct FrameStats {
+    /// Time along the camera path
+    pub time: f32,
+    /// Real duration of the frame in milliseconds
+    pub frame_time: f32,
+    pub loaded_chunks: usize,
+    /// Vertices of the visible meshes, see `visible_vertex_count`
+    pub vertex_count: usize,
+}
+
+/// What the `CameraPathPlugin` is doing, can be changed at runtime to record or play paths from code
+#[derive(Debug, Default, Resource)]
+pub enum CameraPathState {
+    #[default]
+    Idle,
+    Recording {
+        path: CameraPath,
+        elapsed: f32,
+    },
+    Playing {
+        path: CameraPath,
+        elapsed: f32,
+        /// Statistics of the frames played so far, `None` when not benchmarking
+        stats: Option<Vec<FrameStats>>,
+    },
+}
+
+impl CameraPathState {
+    pub fn record() -> Self {
+        Self::Recording {
+            path: CameraPath::default(),
+            elapsed: 0.0,
+        }
+    }
+
+    pub fn play(path: CameraPath) -> Self {
+        Self::Playing {
+            path: path,
+            elapsed: 0.0,
+            stats: None,
+        }
+    }
+
+    /// Play the path and write the statistics of every frame to the benchmark file
+    pub fn benchmark(path: CameraPath) -> Self {
+        Self::Playing {
+            path: path,
+            elapsed: 0.0,
+            stats: Some(Vec::new()),
+        }
+    }
+}
+
+/// Settings of the `CameraPathPlugin`
+#[derive(Debug, Clone, Resource)]
+pub struct CameraPathSettings {
+    pub id: u32,
+    /// Seconds between two recorded keyframes
+    pub record_interval: f32,
+    /// Path advance per frame during playback, so that the same frames are rendered whatever the frame rate.
+    /// `None` plays the path in real time
+    pub playback_step: Option<f32>,
+    pub path_file: PathBuf,
+    pub benchmark_file: PathBuf,
+    /// Quit the app once a benchmark is written
+    pub exit_after_benchmark: bool,
+}
+
+/// Record the camera `id` to a file and play it back, with `Action::RecordCameraPath`, `Action::PlayCameraPath`
+/// and `Action::BenchmarkCameraPath` or by changing the `CameraPathState` resource
+///
+/// Benchmarks write the frame time, loaded chunks of the `VoxelWorld<T>` and visible vertices of every played frame to a CSV file.
+pub struct CameraPathPlugin<T: VoxelSet> {
+    settings: CameraPathSettings,
+    _voxel_set: PhantomData<T>,
+}
+
+impl<T: VoxelSet> Plugin for CameraPathPlugin<T> {
+    fn build(&self, app: &mut App) {
+        app.insert_resource(self.settings.clone())
+            .init_resource::<CameraPathState>()
+            .add_systems(
+                PostUpdate,
+                (
+                    (control_camera_path, update_camera_path)
+                        .chain()
+                        .before(TransformSystem::TransformPropagate),
+                    // The frame is measured once the visibility of the meshes from the moved camera is known
+                    advance_camera_path::<T>.after(VisibilitySystems::CheckVisibility),
+                ),
+            );
+    }
+}
+
+impl<T: VoxelSet> CameraPathPlugin<T> {
+    pub fn new(id: u32) -> Self {
+        Self {
+            settings: CameraPathSettings {
+                id: id,
+                record_interval: 0.1,
+                playback_step: Some(1.0 / 60.0),
+                path_file: PathBuf::from("camera_path.ron"),
+                benchmark_file: PathBuf::from("benchmark.csv"),
+                exit_after_benchmark: false,
+            },
+            _voxel_set: PhantomData,
+        }
+    }
+
+    pub fn with_record_interval(mut self, record_interval: f32) -> Self {
+        self.settings.record_interval = record_interval;
+        return self;
+    }
+    pub fn with_playback_step(mut self, playback_step: Option<f32>) -> Self {
+        self.settings.playback_step = playback_step;
+        return self;
+    }
+    pub fn with_path_file(mut self, path_file: impl Into<PathBuf>) -> Self {
+        self.settings.path_file = path_file.into();
+        return self;
+    }
+    pub fn with_benchmark_file(mut self, benchmark_file: impl Into<PathBuf>) -> Self {
+        self.settings.benchmark_file = benchmark_file.into();
+        return self;
+    }
+    pub fn with_exit_after_benchmark(mut self, exit_after_benchmark: bool) -> Self {
+        self.settings.exit_after_benchmark = exit_after_benchmark;
+        return self;
+    }
+}
+
+/// Return the number of vertices of the meshes visible this frame, once `VisibilitySystems::CheckVisibility` has run
+pub fn visible_vertex_count(meshes: &Assets<Mesh>, q_meshes: &Query<(&Handle<Mesh>, &ViewVisibility)>) -> usize {
+    let mut counter = 0;
+    for (mesh_handle, vis) in q_meshes.iter() {
+        if vis.get() {
+            if let Some(mesh) = meshes.get(mesh_handle) {
+                counter += mesh.count_vertices();
+            }
+        }
+    }
+    return counter;
+}
+
+/// Write the statistics as CSV, one line per frame
+pub fn write_benchmark(path: impl AsRef<Path>, stats: &[FrameStats]) -> io::Result<()> {
+    let mut content = String::from("frame,time,frame_time_ms,loaded_chunks,vertex_count\n");
+    for (frame, frame_stats) in stats.iter().enumerate() {
+        let _ = writeln!(
+            content,
+            "{},{},{},{},{}",
+            frame, frame_stats.time, frame_stats.frame_time, frame_stats.loaded_chunks, frame_stats.vertex_count
+        );
+    }
+    return fs::write(path, content);
+}
+
+/// Start and stop recording or playback from the actions
+fn control_camera_path(actions: Res<ActionState>, settings: Res<CameraPathSettings>, mut state: ResMut<CameraPathState>) {
+    if actions.just_pressed(Action::RecordCameraPath) {
+        if let CameraPathState::Recording { path, .. } = &*state {
+            match path.save(&settings.path_file) {
+                Ok(()) => info!("Camera path saved to {}", settings.path_file.display()),
+                Err(e) => warn!("Can't save camera path to {} : {}", settings.path_file.display(), e),
+            }
+            *state = CameraPathState::Idle;
+        } else {
+            info!("Recording camera path");
+            *state = CameraPathState::record();
+        }
+    }
+
+    let play = actions.just_pressed(Action::PlayCameraPath);
+    let benchmark = actions.just_pressed(Action::BenchmarkCameraPath);
+    if play || benchmark {
+        if let CameraPathState::Playing { .. } = &*state {
+            *state = CameraPathState::Idle;
+            return;
+        }
+        match CameraPath::load(&settings.path_file) {
+            Ok(path) if benchmark => *state = CameraPathState::benchmark(path),
+            Ok(path) => *state = CameraPathState::play(path),
+            Err(e) => warn!("Can't read camera path {} : {}", settings.path_file.display(), e),
+        }
+    }
+}
+
+/// Record the camera, or move it to the current point of the played path
+fn update_camera_path(
+    time: Res<Time>,
+    settings: Res<CameraPathSettings>,
+    mut state: ResMut<CameraPathState>,
+    mut cameras: Query<(&CameraId, &mut Transform)>,
+) {
+    let Some((_, mut camera_transform)) = cameras.iter_mut().find(|(camera_id, _)| camera_id.0 == settings.id) else {
+        return;
+    };
+    match &mut *state {
+        CameraPathState::Idle => {}
+        CameraPathState::Recording { path, elapsed } => {
+            let due = path.keyframes.last().map_or(true, |keyframe| *elapsed - keyframe.time >= settings.record_interval);
+            if due {
+                path.keyframes.push(CameraKeyframe {
+                    time: *elapsed,
+                    translation: camera_transform.translation,
+                    rotation: camera_transform.rotation,
+                });
+            }
+            *elapsed += time.delta_seconds();
+        }
+        CameraPathState::Playing { path, elapsed, .. } => {
+            if let Some(transform) = path.sample(*elapsed) {
+                *camera_transform = transform;
+            }
+        }
+    }
+}
+
+/// Measure the played frame and move along the path, writing the benchmark at the end of the path
+fn advance_camera_path<T: VoxelSet>(
+    time: Res<Time>,
+    settings: Res<CameraPathSettings>,
+    mut state: ResMut<CameraPathState>,
+    world: Option<Res<VoxelWorld<T>>>,
+    meshes: Option<Res<Assets<Mesh>>>,
+    q_meshes: Query<(&Handle<Mesh>, &ViewVisibility)>,
+    mut app_exit_events: EventWriter<AppExit>,
+) {
+    let CameraPathState::Playing { path, elapsed, stats } = &mut *state else {
+        return;
+    };
+    let delta = time.delta_seconds();
+    if let Some(stats) = stats {
+        stats.push(FrameStats {
+            time: *elapsed,
+            frame_time: delta * 1000.0,
+            loaded_chunks: world.as_ref().map_or(0, |world| world.chunk_positions().len()),
+            vertex_count: meshes.as_ref().map_or(0, |meshes| visible_vertex_count(meshes, &q_meshes)),
+        });
+    }
+    if *elapsed < path.duration() {
+        *elapsed += settings.playback_step.unwrap_or(delta);
+        return;
+    }
+
+    let stats = stats.take();
+    *state = CameraPathState::Idle;
+    if let Some(stats) = stats {
+        match write_benchmark(&settings.benchmark_file, &stats) {
+            Ok(()) => info!("Benchmark of {} frames written to {}", stats.len(), settings.benchmark_file.display()),
+            Err(e) => warn!("Can't write benchmark to {} : {}", settings.benchmark_file.display(), e),
+        }
+        if settings.exit_after_benchmark {
+            app_exit_events.send(AppExit);
+        }
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use std::f32::consts::FRAC_PI_2;
+
+    use bevy::math::{Quat, Vec3};
+
+    use super::{CameraKeyframe, CameraPath};
+
+    fn path() -> CameraPath {
+        return CameraPath {
+            keyframes: vec![
+                CameraKeyframe {
+                    time: 0.0,
+                    translation: Vec3::ZERO,
+                    rotation: Quat::IDENTITY,
+                },
+                CameraKeyframe {
+                    time: 2.0,
+                    translation: Vec3::new(10.0, 0.0, 0.0),
+                    rotation: Quat::from_rotation_y(FRAC_PI_2),
+                },
+                CameraKeyframe {
+                    time: 3.0,
+                    translation: Vec3::new(10.0, 5.0, 0.0),
+                    rotation: Quat::from_rotation_y(FRAC_PI_2),
+                },
+            ],
+        };
+    }
+
+    #[test]
+    fn interpolates_between_keyframes() {
+        let path = path();
+        assert_eq!(path.duration(), 3.0);
+        let transform = path.sample(1.0).unwrap();
+        assert!(transform.translation.distance(Vec3::new(5.0, 0.0, 0.0)) < 1e-5);
+        assert!(transform.rotation.angle_between(Quat::from_rotation_y(FRAC_PI_2 / 2.0)) < 1e-4);
+        assert!(path.sample(2.5).unwrap().translation.distance(Vec3::new(10.0, 2.5, 0.0)) < 1e-5);
+    }
+
+    #[test]
+    fn clamps_outside_of_the_path() {
+        let path = path();
+        assert_eq!(path.sample(-1.0).unwrap().translation, Vec3::ZERO);
+        assert_eq!(path.sample(10.0).unwrap().translation, Vec3::new(10.0, 5.0, 0.0));
+        assert!(CameraPath::default().sample(0.0).is_none());
+    }
+
+    #[test]
+    fn round_trips_through_ron() {
+        let path = path();
+        let content = ron::to_string(&path).unwrap();
+        assert_eq!(ron::from_str::<CameraPath>(&content).unwrap(), path);
+    }
+}
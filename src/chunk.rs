@@ -1,13 +1,24 @@
 use std::mem;
-use bevy::{math::{UVec3, Vec3}, prelude::Component, render::mesh::Mesh};
+use bevy::{math::{IVec2, UVec3, Vec3}, prelude::Component, render::mesh::Mesh};
 
-use crate::voxel::{Voxel, VoxelSet};
+use crate::{
+    octree::Octree,
+    voxel::{Voxel, VoxelSet},
+};
 
 pub const WIDTH: usize = 16;
 pub const HEIGHT: usize = 128;
 
+/// Entity rendering the chunk at `pos` (in chunk coordinates)
 #[derive(Debug, Component)]
-pub struct ChunkMarker;
+pub struct ChunkMarker {
+    pub pos: IVec2,
+}
+
+/// Child of a `ChunkMarker` entity holding the translucent mesh of the chunk
+#[derive(Debug, Component)]
+pub struct TransparentChunkMarker;
+
 pub struct Chunk<T: VoxelSet> {
     voxels: [[[T::Id; WIDTH]; HEIGHT]; WIDTH],
 }
@@ -31,6 +42,11 @@ impl<T: VoxelSet> Chunk<T> {
         return self.voxels[pos.x as usize][pos.y as usize][pos.z as usize];
     }
 
+    /// Set voxel id in chunk coordinates
+    pub fn set_voxel_id(&mut self, pos: UVec3, voxel_id: T::Id) {
+        self.voxels[pos.x as usize][pos.y as usize][pos.z as usize] = voxel_id;
+    }
+
     /// Clone chunk content into a new dynamic array
     pub fn clone_voxels(&self) -> Vec<Vec<Vec<T::Id>>> {
         let mut volume = Vec::<Vec<Vec<T::Id>>>::new();
@@ -47,4 +63,15 @@ impl<T: VoxelSet> Chunk<T> {
         }
         return volume;
     }
-}
\ No newline at end of file
+
+    /// Build an octree for each `WIDTH` high cube of the chunk, from the bottom
+    pub fn octrees(&self) -> Vec<Octree<T::Id>> {
+        let voxels = self.clone_voxels();
+        return (0..HEIGHT / WIDTH)
+            .map(|section| {
+                let cube = voxels.iter().map(|surface| surface[section * WIDTH..(section + 1) * WIDTH].to_vec()).collect::<Vec<_>>();
+                Octree::from(cube)
+            })
+            .collect();
+    }
+}
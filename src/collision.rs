@@ -0,0 +1,256 @@
+use bevy::math::{IVec3, Vec3};
+
+use crate::{voxel::VoxelSet, world::VoxelWorld};
+
+/// Distance under which a box is considered touching a voxel face
+pub const COLLISION_EPSILON: f32 = 1e-4;
+
+/// Axis aligned box in world coordinates
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct Aabb {
+    pub min: Vec3,
+    pub max: Vec3,
+}
+
+impl Aabb {
+    pub fn new(min: Vec3, max: Vec3) -> Self {
+        Self { min, max }
+    }
+
+    /// Create a box of `size` whose bottom face is centered on `feet`
+    pub fn from_feet(feet: Vec3, size: Vec3) -> Self {
+        let half = Vec3::new(size.x / 2.0, 0.0, size.z / 2.0);
+        return Self::new(feet - half, feet + half + Vec3::new(0.0, size.y, 0.0));
+    }
+
+    pub fn translated(&self, offset: Vec3) -> Self {
+        return Self::new(self.min + offset, self.max + offset);
+    }
+
+    /// Return the range of voxels overlapped by the box on `axis`, faces only touching a voxel don't count
+    fn voxel_range(&self, axis: usize) -> (i32, i32) {
+        return (
+            (self.min[axis] + COLLISION_EPSILON).floor() as i32,
+            (self.max[axis] - COLLISION_EPSILON).ceil() as i32 - 1,
+        );
+    }
+}
+
+/// Result of `VoxelWorld::move_aabb`
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct CollisionResult {
+    /// Box after the movement
+    pub aabb: Aabb,
+    /// Movement actually applied
+    pub motion: Vec3,
+    /// The box touched a voxel under it
+    pub grounded: bool,
+    /// The box touched a voxel above it
+    pub hit_ceiling: bool,
+    /// The box touched a voxel on the X or Z axis
+    pub hit_wall: bool,
+}
+
+impl<T: VoxelSet> VoxelWorld<T> {
+    pub fn is_solid(&self, pos: IVec3) -> bool {
+        return !T::is_transparent(self.get_voxel_id(pos));
+    }
+
+    /// Check if the box overlaps a solid voxel
+    pub fn is_aabb_colliding(&self, aabb: Aabb) -> bool {
+        let (min_x, max_x) = aabb.voxel_range(0);
+        let (min_y, max_y) = aabb.voxel_range(1);
+        let (min_z, max_z) = aabb.voxel_range(2);
+        for x in min_x..=max_x {
+            for y in min_y..=max_y {
+                for z in min_z..=max_z {
+                    if self.is_solid(IVec3::new(x, y, z)) {
+                        return true;
+                    }
+                }
+            }
+        }
+        return false;
+    }
+
+    /// Move a box by `motion`, stopping it against solid (non transparent) voxels
+    ///
+    /// The movement is resolved axis by axis, Y first then X and Z, so that a box blocked on one axis still slides on the others.
+    /// The box is expected not to overlap solid voxels before moving.
+    pub fn move_aabb(&self, aabb: Aabb, motion: Vec3) -> CollisionResult {
+        let mut result = CollisionResult {
+            aabb: aabb,
+            motion: Vec3::ZERO,
+            grounded: false,
+            hit_ceiling: false,
+            hit_wall: false,
+        };
+        for axis in [1, 0, 2] {
+            let (delta, hit) = self.sweep_aabb_on_axis(result.aabb, axis, motion[axis]);
+            let mut offset = Vec3::ZERO;
+            offset[axis] = delta;
+            result.aabb = result.aabb.translated(offset);
+            result.motion[axis] = delta;
+            if hit {
+                match (axis, motion[axis] < 0.0) {
+                    (1, true) => result.grounded = true,
+                    (1, false) => result.hit_ceiling = true,
+                    _ => result.hit_wall = true,
+                }
+            }
+        }
+        return result;
+    }
+
+    /// Return how far the box can move by `delta` on `axis` and if it was stopped by a voxel
+    fn sweep_aabb_on_axis(&self, aabb: Aabb, axis: usize, delta: f32) -> (f32, bool) {
+        if delta == 0.0 {
+            return (0.0, false);
+        }
+        let (u, v) = ((axis + 1) % 3, (axis + 2) % 3);
+        let (min_u, max_u) = aabb.voxel_range(u);
+        let (min_v, max_v) = aabb.voxel_range(v);
+        let is_layer_blocked = |layer: i32| -> bool {
+            for i in min_u..=max_u {
+                for j in min_v..=max_v {
+                    let mut pos = IVec3::ZERO;
+                    pos[axis] = layer;
+                    pos[u] = i;
+                    pos[v] = j;
+                    if self.is_solid(pos) {
+                        return true;
+                    }
+                }
+            }
+            return false;
+        };
+
+        if delta > 0.0 {
+            let first_layer = (aabb.max[axis] - COLLISION_EPSILON).ceil() as i32;
+            let last_layer = (aabb.max[axis] + delta).ceil() as i32 - 1;
+            for layer in first_layer..=last_layer {
+                if is_layer_blocked(layer) {
+                    return ((layer as f32 - aabb.max[axis]).max(0.0), true);
+                }
+            }
+        } else {
+            let first_layer = (aabb.min[axis] + COLLISION_EPSILON).floor() as i32 - 1;
+            let last_layer = (aabb.min[axis] + delta).floor() as i32;
+            for layer in (last_layer..=first_layer).rev() {
+                if is_layer_blocked(layer) {
+                    return ((layer as f32 + 1.0 - aabb.min[axis]).min(0.0), true);
+                }
+            }
+        }
+        return (delta, false);
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use bevy::math::{IVec2, IVec3, Vec3};
+
+    use crate::{
+        region::Region,
+        test_util::{empty_world, TestSet},
+        world::VoxelWorld,
+    };
+
+    use super::Aabb;
+
+    /// 4x4 chunks around the origin with a ground layer at y = 0
+    fn flat_world() -> VoxelWorld<TestSet> {
+        let mut world = empty_world((-2..2).flat_map(|x| (-2..2).map(move |z| IVec2::new(x, z))));
+        world.fill_region(&Region::new_box(IVec3::new(-32, 0, -32), IVec3::new(31, 0, 31)), 1);
+        return world;
+    }
+
+    fn player(feet: Vec3) -> Aabb {
+        return Aabb::from_feet(feet, Vec3::new(0.6, 1.8, 0.6));
+    }
+
+    #[test]
+    fn falls_onto_ground() {
+        let world = flat_world();
+        let result = world.move_aabb(player(Vec3::new(3.5, 5.0, 3.5)), Vec3::new(0.0, -10.0, 0.0));
+        assert!(result.grounded);
+        assert!(!result.hit_ceiling && !result.hit_wall);
+        assert_eq!(result.aabb.min.y, 1.0);
+        assert_eq!(result.motion, Vec3::new(0.0, -4.0, 0.0));
+    }
+
+    #[test]
+    fn resting_on_ground_stays_grounded() {
+        let world = flat_world();
+        let result = world.move_aabb(player(Vec3::new(3.5, 1.0, 3.5)), Vec3::new(0.0, -0.1, 0.0));
+        assert!(result.grounded);
+        assert_eq!(result.motion, Vec3::ZERO);
+    }
+
+    #[test]
+    fn walks_freely_over_chunk_borders() {
+        let world = flat_world();
+        let result = world.move_aabb(player(Vec3::new(15.5, 1.0, 0.5)), Vec3::new(-20.0, 0.0, -3.0));
+        assert!(!result.hit_wall && !result.grounded);
+        assert_eq!(result.motion, Vec3::new(-20.0, 0.0, -3.0));
+    }
+
+    #[test]
+    fn stops_against_wall_in_next_chunk() {
+        let mut world = flat_world();
+        world.set_voxel_id(IVec3::new(16, 1, 0), 1);
+        world.set_voxel_id(IVec3::new(16, 2, 0), 1);
+        let result = world.move_aabb(player(Vec3::new(14.0, 1.0, 0.5)), Vec3::new(5.0, 0.0, 0.0));
+        assert!(result.hit_wall);
+        assert!((result.aabb.max.x - 16.0).abs() < 1e-5);
+    }
+
+    #[test]
+    fn stops_against_wall_at_negative_coordinates() {
+        let mut world = flat_world();
+        world.set_voxel_id(IVec3::new(-17, 1, -1), 1);
+        let result = world.move_aabb(player(Vec3::new(-14.5, 1.0, -0.5)), Vec3::new(-5.0, 0.0, 0.0));
+        assert!(result.hit_wall);
+        assert!((result.aabb.min.x - -16.0).abs() < 1e-5);
+    }
+
+    #[test]
+    fn slides_along_wall() {
+        let mut world = flat_world();
+        for z in -4..4 {
+            world.set_voxel_id(IVec3::new(-1, 1, z), 1);
+        }
+        let result = world.move_aabb(player(Vec3::new(0.5, 1.0, 0.0)), Vec3::new(-1.0, 0.0, 2.0));
+        assert!(result.hit_wall);
+        assert!((result.aabb.min.x - 0.0).abs() < 1e-5);
+        assert_eq!(result.motion.z, 2.0);
+    }
+
+    #[test]
+    fn touching_corner_does_not_block() {
+        let mut world = flat_world();
+        // Voxel diagonal to the box, only sharing an edge with it
+        world.set_voxel_id(IVec3::new(-1, 1, -1), 1);
+        let result = world.move_aabb(Aabb::new(Vec3::new(0.0, 1.0, 0.0), Vec3::new(1.0, 2.0, 1.0)), Vec3::new(0.0, 0.0, -2.0));
+        assert!(!result.hit_wall);
+        assert_eq!(result.motion.z, -2.0);
+    }
+
+    #[test]
+    fn hits_ceiling() {
+        let mut world = flat_world();
+        world.set_voxel_id(IVec3::new(-8, 4, -8), 1);
+        let result = world.move_aabb(player(Vec3::new(-7.5, 1.0, -7.5)), Vec3::new(0.0, 3.0, 0.0));
+        assert!(result.hit_ceiling);
+        assert!((result.aabb.max.y - 4.0).abs() < 1e-5);
+    }
+
+    #[test]
+    fn transparent_voxels_do_not_collide() {
+        let mut world = flat_world();
+        world.set_voxel_id(IVec3::new(5, 1, 3), 20);
+        let result = world.move_aabb(player(Vec3::new(3.5, 1.0, 3.5)), Vec3::new(3.0, 0.0, 0.0));
+        assert!(!result.hit_wall);
+        assert!(!world.is_aabb_colliding(result.aabb));
+    }
+}
@@ -0,0 +1,440 @@
+use std::{collections::BTreeMap, marker::PhantomData, path::PathBuf, str::FromStr};
+
+use bevy::{
+    app::{App, Plugin, Startup, Update},
+    ecs::{
+        change_detection::DetectChanges,
+        component::Component,
+        event::EventReader,
+        query::With,
+        schedule::IntoSystemConfigs,
+        system::{Commands, Query, Res, ResMut, Resource},
+        world::World,
+    },
+    hierarchy::BuildChildren,
+    input::{keyboard::KeyCode, ButtonInput},
+    math::{IVec2, IVec3, Vec3},
+    pbr::wireframe::WireframeConfig,
+    render::{color::Color, view::Visibility},
+    text::{Text, TextStyle},
+    transform::components::Transform,
+    ui::{
+        node_bundles::{NodeBundle, TextBundle},
+        BackgroundColor, PositionType, Style, UiRect, Val,
+    },
+    window::ReceivedCharacter,
+};
+
+use crate::{
+    actions::{Action, ActionState},
+    camera::{CameraId, ControlledCamera},
+    chunk::Chunk,
+    region::Region,
+    voxel::VoxelSet,
+    world::VoxelWorld,
+};
+
+/// Lines of history shown above the input line
+const CONSOLE_LINES: usize = 12;
+
+/// Most voxels `fill` can change at once, 128 full height chunks
+const MAX_FILL_VOLUME: i64 = 1 << 22;
+
+/// Run a command with its arguments (without the command name), returning a message or an error
+pub type ConsoleHandler = Box<dyn Fn(&[&str], &mut World) -> Result<String, String> + Send + Sync>;
+
+pub struct ConsoleCommand {
+    /// Arguments of the command, shown by `help` and on errors
+    pub usage: String,
+    pub handler: ConsoleHandler,
+}
+
+/// Commands available in the console, other plugins add theirs with `ConsoleAppExt::add_console_command`
+#[derive(Default, Resource)]
+pub struct ConsoleCommands {
+    commands: BTreeMap<String, ConsoleCommand>,
+}
+
+impl ConsoleCommands {
+    /// Add a command, replacing the one with the same name
+    pub fn register(&mut self, name: &str, usage: &str, handler: impl Fn(&[&str], &mut World) -> Result<String, String> + Send + Sync + 'static) {
+        self.commands.insert(
+            name.to_string(),
+            ConsoleCommand {
+                usage: usage.to_string(),
+                handler: Box::new(handler),
+            },
+        );
+    }
+
+    pub fn get(&self, name: &str) -> Option<&ConsoleCommand> {
+        return self.commands.get(name);
+    }
+
+    /// Parse and run a command line, returning the lines to print
+    pub fn execute(&self, line: &str, world: &mut World) -> Vec<String> {
+        let mut words = line.split_whitespace();
+        let Some(name) = words.next() else {
+            return vec![];
+        };
+        let args = words.collect::<Vec<_>>();
+        if name == "help" {
+            return self.commands.iter().map(|(name, command)| format!("{} {}", name, command.usage)).collect();
+        }
+        let Some(command) = self.commands.get(name) else {
+            return vec![format!("error: unknown command '{}', type 'help' for the list of commands", name)];
+        };
+        return match (command.handler)(&args, world) {
+            Ok(message) if message.is_empty() => vec![],
+            Ok(message) => vec![message],
+            Err(e) => vec![format!("error: {}", e), format!("usage: {} {}", name, command.usage)],
+        };
+    }
+}
+
+/// Register console commands on an app
+pub trait ConsoleAppExt {
+    fn add_console_command(
+        &mut self,
+        name: &str,
+        usage: &str,
+        handler: impl Fn(&[&str], &mut World) -> Result<String, String> + Send + Sync + 'static,
+    ) -> &mut Self;
+}
+
+impl ConsoleAppExt for App {
+    fn add_console_command(
+        &mut self,
+        name: &str,
+        usage: &str,
+        handler: impl Fn(&[&str], &mut World) -> Result<String, String> + Send + Sync + 'static,
+    ) -> &mut Self {
+        self.world.get_resource_or_insert_with(ConsoleCommands::default).register(name, usage, handler);
+        return self;
+    }
+}
+
+/// Check that exactly `count` arguments are given
+pub fn expect_args(args: &[&str], count: usize) -> Result<(), String> {
+    if args.len() != count {
+        return Err(format!("expected {} arguments, got {}", count, args.len()));
+    }
+    return Ok(());
+}
+
+/// Parse the argument at `index`, `name` is used in the error message
+pub fn parse_arg<A: FromStr>(args: &[&str], index: usize, name: &str) -> Result<A, String> {
+    let arg = args.get(index).ok_or_else(|| format!("missing argument {}", name))?;
+    return arg.parse().map_err(|_| format!("invalid value '{}' for {}", arg, name));
+}
+
+/// Text input and history of the console
+#[derive(Debug, Default, Resource)]
+pub struct ConsoleState {
+    pub open: bool,
+    pub input: String,
+    /// Lines printed so far
+    pub history: Vec<String>,
+    /// Lines entered, waiting to be run
+    pending: Vec<String>,
+    /// Entered lines, recalled with the up and down arrows
+    entered: Vec<String>,
+    recall_index: Option<usize>,
+}
+
+/// World settings read by the `seed`, `regen` and `save` commands, set with the `ConsolePlugin` builder
+#[derive(Debug, Clone, Resource)]
+pub struct ConsoleSettings {
+    /// Seed given to the `ChunkGenerator`
+    pub seed: u64,
+    /// File written by `save`, see `VoxelWorld::save_chunks`
+    pub save_file: PathBuf,
+}
+
+/// Generate the chunk at a position from the seed, chunks can only be regenerated by `regen` if the app inserts one
+#[derive(Resource)]
+pub struct ChunkGenerator<T: VoxelSet>(pub Box<dyn Fn(u64, IVec2) -> Chunk<T> + Send + Sync>);
+
+#[derive(Debug, Component)]
+struct ConsoleNode;
+
+#[derive(Debug, Component)]
+struct ConsoleText;
+
+/// Developer console toggled with `Action::ToggleConsole` and closed with `Action::ReleaseCursor`, running the commands of the `ConsoleCommands` registry
+///
+/// Built-in commands edit the `VoxelWorld<T>` (`set`, `fill`, `undo`, `redo`, `regen`), save it (`save`), print the seed (`seed`),
+/// move the controlled camera (`tp`) and toggle the `WireframeConfig` (`wireframe`).
+pub struct ConsolePlugin<T: VoxelSet> {
+    settings: ConsoleSettings,
+    _voxel_set: PhantomData<T>,
+}
+
+impl<T: VoxelSet<Id = u8>> Plugin for ConsolePlugin<T> {
+    fn build(&self, app: &mut App) {
+        app.init_resource::<ConsoleState>()
+            .insert_resource(self.settings.clone())
+            .add_systems(Startup, spawn_console)
+            .add_systems(Update, (toggle_console, read_console_input, run_console_commands, update_console_text).chain())
+            .add_console_command("tp", "<x> <y> <z>", |args, world| {
+                expect_args(args, 3)?;
+                let pos = Vec3::new(parse_arg(args, 0, "x")?, parse_arg(args, 1, "y")?, parse_arg(args, 2, "z")?);
+                let controlled_camera = world.get_resource::<ControlledCamera>().map(|controlled_camera| controlled_camera.0);
+                let mut q_cameras = world.query::<(&CameraId, &mut Transform)>();
+                let Some((_, mut camera_transform)) = q_cameras
+                    .iter_mut(world)
+                    .find(|(camera_id, _)| controlled_camera.map_or(true, |id| camera_id.0 == id))
+                else {
+                    return Err("no camera to teleport".to_string());
+                };
+                camera_transform.translation = pos;
+                return Ok(format!("Teleported to {} {} {}", pos.x, pos.y, pos.z));
+            })
+            .add_console_command("set", "<x> <y> <z> <id>", |args, world| {
+                expect_args(args, 4)?;
+                let pos = parse_voxel_pos(args, 0)?;
+                let id: T::Id = parse_arg(args, 3, "id")?;
+                let mut voxel_world = world.resource_mut::<VoxelWorld<T>>();
+                return match voxel_world.transaction("set", |voxel_world| voxel_world.set_voxel_id(pos, id)) {
+                    Some(_) => Ok(String::new()),
+                    None => Err(format!("voxel {} {} {} is not in a loaded chunk", pos.x, pos.y, pos.z)),
+                };
+            })
+            .add_console_command("fill", "<x1> <y1> <z1> <x2> <y2> <z2> <id>", |args, world| {
+                expect_args(args, 7)?;
+                let (from, to) = (parse_voxel_pos(args, 0)?, parse_voxel_pos(args, 3)?);
+                let volume: i64 = (0..3).map(|axis| (to[axis] as i64 - from[axis] as i64).abs() + 1).product();
+                if volume > MAX_FILL_VOLUME {
+                    return Err(format!("the region holds {} voxels, at most {} can be filled at once", volume, MAX_FILL_VOLUME));
+                }
+                let id: T::Id = parse_arg(args, 6, "id")?;
+                let mut voxel_world = world.resource_mut::<VoxelWorld<T>>();
+                voxel_world.begin_transaction("fill");
+                let modified_chunks = voxel_world.fill_region(&Region::new_box(from, to), id);
+                if !voxel_world.commit_transaction() {
+                    return Ok(format!("{} chunks modified, too large to be undone", modified_chunks.len()));
+                }
+                return Ok(format!("{} chunks modified", modified_chunks.len()));
+            })
+            .add_console_command("undo", "", |args, world| {
+                expect_args(args, 0)?;
+                let name = world.resource_mut::<VoxelWorld<T>>().undo().ok_or("nothing to undo")?;
+                return Ok(format!("Undone {}", name));
+            })
+            .add_console_command("redo", "", |args, world| {
+                expect_args(args, 0)?;
+                let name = world.resource_mut::<VoxelWorld<T>>().redo().ok_or("nothing to redo")?;
+                return Ok(format!("Redone {}", name));
+            })
+            .add_console_command("seed", "", |args, world| {
+                expect_args(args, 0)?;
+                return Ok(format!("Seed : {}", world.resource::<ConsoleSettings>().seed));
+            })
+            .add_console_command("regen", "chunk <cx> <cz>", |args, world| {
+                if args.first() != Some(&"chunk") {
+                    return Err("only chunks can be regenerated".to_string());
+                }
+                expect_args(args, 3)?;
+                let pos = IVec2::new(parse_arg(args, 1, "cx")?, parse_arg(args, 2, "cz")?);
+                let seed = world.resource::<ConsoleSettings>().seed;
+                let Some(generator) = world.get_resource::<ChunkGenerator<T>>() else {
+                    return Err("no chunk generator in this app".to_string());
+                };
+                let chunk = (generator.0)(seed, pos);
+                world.resource_mut::<VoxelWorld<T>>().insert_chunk(pos, chunk);
+                return Ok(format!("Chunk {} {} regenerated", pos.x, pos.y));
+            })
+            .add_console_command("save", "", |args, world| {
+                expect_args(args, 0)?;
+                let save_file = world.resource::<ConsoleSettings>().save_file.clone();
+                let count = world.resource::<VoxelWorld<T>>().save_chunks(&save_file).map_err(|e| e.to_string())?;
+                return Ok(format!("{} chunks saved to {}", count, save_file.display()));
+            })
+            .add_console_command("wireframe", "on|off", |args, world| {
+                expect_args(args, 1)?;
+                let global = match args[0] {
+                    "on" => true,
+                    "off" => false,
+                    arg => return Err(format!("invalid value '{}', expected on or off", arg)),
+                };
+                let Some(mut wireframe_config) = world.get_resource_mut::<WireframeConfig>() else {
+                    return Err("wireframes are not enabled in this app".to_string());
+                };
+                wireframe_config.global = global;
+                return Ok(String::new());
+            });
+    }
+}
+
+impl<T: VoxelSet> ConsolePlugin<T> {
+    pub fn new() -> Self {
+        Self {
+            settings: ConsoleSettings {
+                seed: 0,
+                save_file: PathBuf::from("world.bin"),
+            },
+            _voxel_set: PhantomData,
+        }
+    }
+
+    pub fn with_seed(mut self, seed: u64) -> Self {
+        self.settings.seed = seed;
+        return self;
+    }
+    pub fn with_save_file(mut self, save_file: impl Into<PathBuf>) -> Self {
+        self.settings.save_file = save_file.into();
+        return self;
+    }
+}
+
+impl<T: VoxelSet> Default for ConsolePlugin<T> {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+/// Parse the three arguments from `index` as voxel coordinates
+fn parse_voxel_pos(args: &[&str], index: usize) -> Result<IVec3, String> {
+    return Ok(IVec3::new(parse_arg(args, index, "x")?, parse_arg(args, index + 1, "y")?, parse_arg(args, index + 2, "z")?));
+}
+
+fn spawn_console(mut commands: Commands) {
+    commands
+        .spawn((
+            NodeBundle {
+                style: Style {
+                    position_type: PositionType::Absolute,
+                    bottom: Val::Px(0.0),
+                    left: Val::Px(0.0),
+                    width: Val::Percent(100.0),
+                    padding: UiRect::all(Val::Px(5.0)),
+                    ..Default::default()
+                },
+                background_color: BackgroundColor(Color::rgba(0.0, 0.0, 0.0, 0.7)),
+                visibility: Visibility::Hidden,
+                ..Default::default()
+            },
+            ConsoleNode,
+        ))
+        .with_children(|parent| {
+            parent.spawn((
+                TextBundle::from_section("", TextStyle {
+                    font_size: 16.0,
+                    color: Color::WHITE,
+                    ..Default::default()
+                }),
+                ConsoleText,
+            ));
+        });
+}
+
+fn toggle_console(actions: Res<ActionState>, mut console: ResMut<ConsoleState>, mut q_console: Query<&mut Visibility, With<ConsoleNode>>) {
+    // Actions are captured while the console is open, it is closed with their bindings directly
+    let open = if console.open {
+        !(actions.bound_just_pressed(Action::ToggleConsole) || actions.bound_just_pressed(Action::ReleaseCursor))
+    } else {
+        actions.just_pressed(Action::ToggleConsole)
+    };
+    if open == console.open {
+        return;
+    }
+    console.open = open;
+    for mut visibility in q_console.iter_mut() {
+        *visibility = if open { Visibility::Inherited } else { Visibility::Hidden };
+    }
+}
+
+fn read_console_input(
+    inputs: Res<ButtonInput<KeyCode>>,
+    mut received_characters: EventReader<ReceivedCharacter>,
+    mut console: ResMut<ConsoleState>,
+    mut actions: ResMut<ActionState>,
+) {
+    // Drop the characters typed while the console is closed or with the binding opening it
+    if !console.open || actions.bound_pressed(Action::ToggleConsole) {
+        received_characters.clear();
+    }
+    actions.captured = console.open;
+    if !console.open {
+        return;
+    }
+    for event in received_characters.read() {
+        for c in event.char.chars().filter(|c| !c.is_control()) {
+            console.input.push(c);
+        }
+    }
+    if inputs.just_pressed(KeyCode::Backspace) {
+        console.input.pop();
+    }
+    if inputs.just_pressed(KeyCode::ArrowUp) && !console.entered.is_empty() {
+        let index = console.recall_index.map_or(console.entered.len() - 1, |index| index.saturating_sub(1));
+        console.recall_index = Some(index);
+        console.input = console.entered[index].clone();
+    }
+    if inputs.just_pressed(KeyCode::ArrowDown) {
+        if let Some(index) = console.recall_index {
+            console.recall_index = if index + 1 < console.entered.len() { Some(index + 1) } else { None };
+            console.input = console.recall_index.map_or(String::new(), |index| console.entered[index].clone());
+        }
+    }
+    if inputs.just_pressed(KeyCode::Enter) {
+        let line = std::mem::take(&mut console.input);
+        console.recall_index = None;
+        if !line.trim().is_empty() {
+            console.entered.push(line.clone());
+            console.pending.push(line);
+        }
+    }
+}
+
+/// Run the entered lines, commands get the whole `World`
+fn run_console_commands(world: &mut World) {
+    let pending = std::mem::take(&mut world.resource_mut::<ConsoleState>().pending);
+    if pending.is_empty() {
+        return;
+    }
+    world.resource_scope(|world, commands: bevy::ecs::world::Mut<ConsoleCommands>| {
+        for line in pending {
+            let output = commands.execute(&line, world);
+            let mut console = world.resource_mut::<ConsoleState>();
+            console.history.push(format!("> {}", line));
+            console.history.extend(output);
+        }
+    });
+}
+
+fn update_console_text(console: Res<ConsoleState>, mut q_text: Query<&mut Text, With<ConsoleText>>) {
+    if !console.is_changed() {
+        return;
+    }
+    let first_line = console.history.len().saturating_sub(CONSOLE_LINES);
+    let mut lines = console.history[first_line..].to_vec();
+    lines.push(format!("> {}_", console.input));
+    for mut text in q_text.iter_mut() {
+        text.sections[0].value = lines.join("\n");
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use bevy::ecs::world::World;
+
+    use super::{expect_args, parse_arg, ConsoleCommands};
+
+    #[test]
+    fn reports_parse_errors() {
+        let mut commands = ConsoleCommands::default();
+        commands.register("add", "<a> <b>", |args, _| {
+            expect_args(args, 2)?;
+            let (a, b): (i32, i32) = (parse_arg(args, 0, "a")?, parse_arg(args, 1, "b")?);
+            return Ok(format!("{}", a + b));
+        });
+        let mut world = World::new();
+        assert_eq!(commands.execute("add 2 3", &mut world), vec!["5"]);
+        assert_eq!(commands.execute("add 2 x", &mut world), vec!["error: invalid value 'x' for b", "usage: add <a> <b>"]);
+        assert_eq!(commands.execute("add 2", &mut world)[0], "error: expected 2 arguments, got 1");
+        assert!(commands.execute("sub 1 2", &mut world)[0].starts_with("error: unknown command 'sub'"));
+        assert_eq!(commands.execute("help", &mut world), vec!["add <a> <b>"]);
+        assert!(commands.execute("   ", &mut world).is_empty());
+    }
+}
@@ -0,0 +1,153 @@
+use std::marker::PhantomData;
+
+use bevy::{
+    app::{App, Plugin, Startup, Update},
+    asset::{Assets, Handle},
+    core_pipeline::core_3d::Camera3d,
+    diagnostic::{DiagnosticsStore, FrameTimeDiagnosticsPlugin},
+    ecs::{
+        component::Component,
+        query::{Or, With},
+        schedule::IntoSystemConfigs,
+        system::{Commands, Query, Res},
+    },
+    math::IVec2,
+    render::{color::Color, mesh::Mesh, view::{ViewVisibility, Visibility}},
+    text::{Text, TextStyle},
+    transform::components::Transform,
+    ui::{node_bundles::TextBundle, BackgroundColor, PositionType, Style, Val},
+};
+
+use crate::{
+    actions::{Action, ActionState},
+    camera::{CameraId, ControlledCamera},
+    camera_path::visible_vertex_count,
+    chunk::{ChunkMarker, TransparentChunkMarker, WIDTH},
+    meshing::remesh_dirty_chunks,
+    voxel::{Orientation, VoxelSet},
+    world::VoxelWorld,
+};
+
+/// Text of the debug overlay
+#[derive(Debug, Component)]
+pub struct DebugOverlay;
+
+/// Show FPS, camera and `VoxelWorld<T>` statistics in a corner of the window, toggled with `Action::ToggleDebugOverlay`
+pub struct DebugOverlayPlugin<T: VoxelSet> {
+    visible: bool,
+    _voxel_set: PhantomData<T>,
+}
+
+impl<T: VoxelSet> Plugin for DebugOverlayPlugin<T> {
+    fn build(&self, app: &mut App) {
+        if !app.is_plugin_added::<FrameTimeDiagnosticsPlugin>() {
+            app.add_plugins(FrameTimeDiagnosticsPlugin);
+        }
+        let visible = self.visible;
+        app.add_systems(Startup, move |mut commands: Commands| {
+            commands.spawn((
+                TextBundle {
+                    text: Text::from_section("", TextStyle {
+                        font_size: 16.0,
+                        color: Color::WHITE,
+                        ..Default::default()
+                    }),
+                    style: Style {
+                        position_type: PositionType::Absolute,
+                        top: Val::Px(5.0),
+                        left: Val::Px(5.0),
+                        ..Default::default()
+                    },
+                    background_color: BackgroundColor(Color::rgba(0.0, 0.0, 0.0, 0.5)),
+                    visibility: if visible { Visibility::Inherited } else { Visibility::Hidden },
+                    ..Default::default()
+                },
+                DebugOverlay,
+            ));
+        })
+        // Before remeshing so that the pending chunks are counted
+        .add_systems(Update, (toggle_debug_overlay, update_debug_overlay::<T>).chain().before(remesh_dirty_chunks::<T>));
+    }
+}
+
+impl<T: VoxelSet> DebugOverlayPlugin<T> {
+    pub fn new() -> Self {
+        Self {
+            visible: false,
+            _voxel_set: PhantomData,
+        }
+    }
+
+    /// Show the overlay at startup
+    pub fn with_visible(mut self, visible: bool) -> Self {
+        self.visible = visible;
+        return self;
+    }
+}
+
+impl<T: VoxelSet> Default for DebugOverlayPlugin<T> {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+fn toggle_debug_overlay(actions: Res<ActionState>, mut q_overlay: Query<&mut Visibility, With<DebugOverlay>>) {
+    if !actions.just_pressed(Action::ToggleDebugOverlay) {
+        return;
+    }
+    for mut visibility in q_overlay.iter_mut() {
+        *visibility = match *visibility {
+            Visibility::Hidden => Visibility::Inherited,
+            _ => Visibility::Hidden,
+        };
+    }
+}
+
+fn update_debug_overlay<T: VoxelSet>(
+    world: Res<VoxelWorld<T>>,
+    diagnostics: Res<DiagnosticsStore>,
+    meshes: Res<Assets<Mesh>>,
+    controlled_camera: Option<Res<ControlledCamera>>,
+    q_cameras: Query<(&Transform, Option<&CameraId>), With<Camera3d>>,
+    q_chunks: Query<&Handle<Mesh>, Or<(With<ChunkMarker>, With<TransparentChunkMarker>)>>,
+    q_opaque_chunks: Query<&Handle<Mesh>, With<ChunkMarker>>,
+    q_meshes: Query<(&Handle<Mesh>, &ViewVisibility)>,
+    mut q_overlay: Query<(&mut Text, &Visibility), With<DebugOverlay>>,
+) {
+    let Ok((mut text, visibility)) = q_overlay.get_single_mut() else {
+        return;
+    };
+    if *visibility == Visibility::Hidden {
+        return;
+    }
+
+    let fps = diagnostics
+        .get(&FrameTimeDiagnosticsPlugin::FPS)
+        .and_then(|fps| fps.smoothed())
+        .unwrap_or(0.0);
+    let mut lines = vec![format!("FPS : {:.0}", fps)];
+
+    let camera_transform = q_cameras
+        .iter()
+        .find(|(_, camera_id)| match (&controlled_camera, camera_id) {
+            (Some(controlled_camera), Some(camera_id)) => camera_id.0 == controlled_camera.0,
+            _ => true,
+        })
+        .map(|(transform, _)| transform);
+    if let Some(camera_transform) = camera_transform {
+        let translation = camera_transform.translation;
+        let chunk = IVec2::new((translation.x / WIDTH as f32).floor() as i32, (translation.z / WIDTH as f32).floor() as i32);
+        lines.push(format!("Position : {:.1} {:.1} {:.1}", translation.x, translation.y, translation.z));
+        lines.push(format!("Chunk : {} {}", chunk.x, chunk.y));
+        lines.push(format!("Facing : {:?}", Orientation::from_horizontal_direction(*camera_transform.forward())));
+    }
+
+    let loaded_chunks = world.chunk_positions().len();
+    let meshed_chunks = q_opaque_chunks.iter().filter(|handle| meshes.contains(*handle)).count();
+    let total_vertices: usize = q_chunks.iter().filter_map(|handle| meshes.get(handle)).map(|mesh| mesh.count_vertices()).sum();
+    lines.push(format!("Chunks : {} loaded, {} meshed", loaded_chunks, meshed_chunks));
+    lines.push(format!("Vertices : {} total, {} visible", total_vertices, visible_vertex_count(&meshes, &q_meshes)));
+    lines.push(format!("Meshing queue : {}", world.dirty_chunk_count()));
+
+    text.sections[0].value = lines.join("\n");
+}
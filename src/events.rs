@@ -0,0 +1,51 @@
+use bevy::{
+    ecs::{
+        change_detection::DetectChangesMut,
+        entity::Entity,
+        event::{Event, EventWriter},
+        system::ResMut,
+    },
+    math::{IVec2, IVec3},
+};
+
+use crate::{voxel::VoxelSet, world::VoxelWorld};
+
+/// Sent when a chunk is inserted in the world
+#[derive(Debug, Event)]
+pub struct ChunkLoaded {
+    pub pos: IVec2,
+}
+
+/// Sent when a chunk is removed from the world
+#[derive(Debug, Event)]
+pub struct ChunkUnloaded {
+    pub pos: IVec2,
+}
+
+/// Sent when the mesh of the chunk entity `entity` has been (re)generated
+#[derive(Debug, Event)]
+pub struct ChunkMeshed {
+    pub entity: Entity,
+    pub pos: IVec2,
+}
+
+/// Sent when the voxel at `pos` (in world coordinates) is changed from `old` to `new`
+#[derive(Debug, Event)]
+pub struct VoxelChanged<T: VoxelSet> {
+    pub pos: IVec3,
+    pub old: T::Id,
+    pub new: T::Id,
+}
+
+/// Send the changes recorded by the `VoxelWorld` resource since the last run as Bevy events
+pub fn send_world_events<T: VoxelSet>(
+    mut world: ResMut<VoxelWorld<T>>,
+    mut chunk_loaded_events: EventWriter<ChunkLoaded>,
+    mut chunk_unloaded_events: EventWriter<ChunkUnloaded>,
+    mut voxel_changed_events: EventWriter<VoxelChanged<T>>,
+) {
+    let world = world.bypass_change_detection();
+    chunk_loaded_events.send_batch(world.take_loaded_chunks().into_iter().map(|pos| ChunkLoaded { pos }));
+    chunk_unloaded_events.send_batch(world.take_unloaded_chunks().into_iter().map(|pos| ChunkUnloaded { pos }));
+    voxel_changed_events.send_batch(world.take_voxel_changes().into_iter().map(|(pos, old, new)| VoxelChanged { pos, old, new }));
+}
@@ -0,0 +1,311 @@
+use std::{
+    fs::File,
+    io::{self, BufWriter, Write},
+    path::Path,
+};
+
+use bevy::{
+    math::{IVec2, Vec3},
+    render::mesh::Mesh,
+};
+
+use crate::{chunk::WIDTH, voxel::VoxelSet, world::VoxelWorld};
+
+/// File formats chunk meshes can be exported to
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ExportFormat {
+    /// Wavefront OBJ, text with positions and normals
+    Obj,
+    /// Binary glTF, a single `.glb` file with a node per mesh
+    Glb,
+}
+
+impl ExportFormat {
+    /// Guess the format from the extension of `path`
+    pub fn from_path(path: impl AsRef<Path>) -> Option<Self> {
+        let extension = path.as_ref().extension()?.to_str()?.to_lowercase();
+        match extension.as_str() {
+            "obj" => return Some(ExportFormat::Obj),
+            "glb" => return Some(ExportFormat::Glb),
+            _ => return None,
+        }
+    }
+}
+
+/// Mesh of a chunk with its vertices moved to world coordinates
+pub struct ExportedMesh {
+    pub name: String,
+    pub positions: Vec<[f32; 3]>,
+    pub normals: Vec<[f32; 3]>,
+    /// Triangle list indices, the vertices are taken in order when the mesh has none
+    pub indices: Vec<u32>,
+}
+
+impl ExportedMesh {
+    /// Read the positions, normals and indices of `mesh`, translated by `offset`
+    pub fn new(name: &str, mesh: &Mesh, offset: Vec3) -> Self {
+        let positions = mesh
+            .attribute(Mesh::ATTRIBUTE_POSITION)
+            .and_then(|positions| positions.as_float3())
+            .unwrap_or(&[])
+            .iter()
+            .map(|position| (Vec3::from(*position) + offset).to_array())
+            .collect::<Vec<_>>();
+        let normals = mesh
+            .attribute(Mesh::ATTRIBUTE_NORMAL)
+            .and_then(|normals| normals.as_float3())
+            .map(|normals| normals.to_vec())
+            .unwrap_or_default();
+        let indices = match mesh.indices() {
+            Some(indices) => indices.iter().map(|index| index as u32).collect(),
+            None => (0..positions.len() as u32).collect(),
+        };
+        return Self {
+            name: name.to_string(),
+            positions,
+            normals,
+            indices,
+        };
+    }
+
+    fn has_normals(&self) -> bool {
+        return !self.normals.is_empty() && self.normals.len() == self.positions.len();
+    }
+}
+
+/// Write the meshes as objects of a single OBJ file
+pub fn write_obj(meshes: &[ExportedMesh], mut writer: impl Write) -> io::Result<()> {
+    // OBJ indices are global to the file and start at 1
+    let mut first_vertex = 1;
+    for mesh in meshes.iter() {
+        writeln!(writer, "o {}", mesh.name)?;
+        for [x, y, z] in mesh.positions.iter() {
+            writeln!(writer, "v {} {} {}", x, y, z)?;
+        }
+        if mesh.has_normals() {
+            for [x, y, z] in mesh.normals.iter() {
+                writeln!(writer, "vn {} {} {}", x, y, z)?;
+            }
+        }
+        for triangle in mesh.indices.chunks_exact(3) {
+            let vertices = triangle.iter().map(|index| index + first_vertex);
+            let vertices = if mesh.has_normals() {
+                vertices.map(|vertex| format!("{}//{}", vertex, vertex)).collect::<Vec<_>>()
+            } else {
+                vertices.map(|vertex| vertex.to_string()).collect::<Vec<_>>()
+            };
+            writeln!(writer, "f {}", vertices.join(" "))?;
+        }
+        first_vertex += mesh.positions.len() as u32;
+    }
+    return Ok(());
+}
+
+/// Quote `value` as a JSON string, escaping quotes, backslashes and control characters
+fn json_string(value: &str) -> String {
+    let mut json = String::from("\"");
+    for c in value.chars() {
+        match c {
+            '"' => json.push_str("\\\""),
+            '\\' => json.push_str("\\\\"),
+            c if c.is_control() => json.push_str(&format!("\\u{:04x}", c as u32)),
+            c => json.push(c),
+        }
+    }
+    json.push('"');
+    return json;
+}
+
+/// Write the meshes as nodes of a single binary glTF file, meshes without vertices are skipped
+pub fn write_glb(meshes: &[ExportedMesh], mut writer: impl Write) -> io::Result<()> {
+    let mut buffer = Vec::<u8>::new();
+    let mut buffer_views = vec![];
+    let mut accessors = vec![];
+    let mut gltf_meshes = vec![];
+    let mut nodes = vec![];
+    // Add a buffer view and its accessor, returning the accessor index
+    let mut push_accessor = |bytes: Vec<u8>, target: u32, component_type: u32, count: usize, kind: &str, bounds: String| -> usize {
+        buffer_views.push(format!(
+            r#"{{"buffer":0,"byteOffset":{},"byteLength":{},"target":{}}}"#,
+            buffer.len(),
+            bytes.len(),
+            target
+        ));
+        buffer.extend(bytes);
+        accessors.push(format!(
+            r#"{{"bufferView":{},"componentType":{},"count":{},"type":"{}"{}}}"#,
+            buffer_views.len() - 1,
+            component_type,
+            count,
+            kind,
+            bounds
+        ));
+        return accessors.len() - 1;
+    };
+    let float3_bytes = |values: &[[f32; 3]]| -> Vec<u8> { values.iter().flatten().flat_map(|value| value.to_le_bytes()).collect() };
+
+    for mesh in meshes.iter().filter(|mesh| !mesh.positions.is_empty()) {
+        let (mut min, mut max) = (Vec3::splat(f32::MAX), Vec3::splat(f32::MIN));
+        for position in mesh.positions.iter() {
+            min = min.min(Vec3::from(*position));
+            max = max.max(Vec3::from(*position));
+        }
+        let bounds = format!(r#","min":[{},{},{}],"max":[{},{},{}]"#, min.x, min.y, min.z, max.x, max.y, max.z);
+        let mut attributes = vec![format!(
+            r#""POSITION":{}"#,
+            push_accessor(float3_bytes(&mesh.positions), 34962, 5126, mesh.positions.len(), "VEC3", bounds)
+        )];
+        if mesh.has_normals() {
+            let accessor = push_accessor(float3_bytes(&mesh.normals), 34962, 5126, mesh.normals.len(), "VEC3", String::new());
+            attributes.push(format!(r#""NORMAL":{}"#, accessor));
+        }
+        let indices_bytes = mesh.indices.iter().flat_map(|index| index.to_le_bytes()).collect();
+        let indices = push_accessor(indices_bytes, 34963, 5125, mesh.indices.len(), "SCALAR", String::new());
+        gltf_meshes.push(format!(r#"{{"name":{},"primitives":[{{"attributes":{{{}}},"indices":{},"mode":4}}]}}"#, json_string(&mesh.name), attributes.join(","), indices));
+        nodes.push(format!(r#"{{"name":{},"mesh":{}}}"#, json_string(&mesh.name), gltf_meshes.len() - 1));
+    }
+
+    let mut json = format!(
+        r#"{{"asset":{{"version":"2.0","generator":"voxel-engine"}},"scene":0,"scenes":[{{"nodes":[{}]}}],"nodes":[{}],"meshes":[{}],"accessors":[{}],"bufferViews":[{}],"buffers":[{{"byteLength":{}}}]}}"#,
+        (0..nodes.len()).map(|node| node.to_string()).collect::<Vec<_>>().join(","),
+        nodes.join(","),
+        gltf_meshes.join(","),
+        accessors.join(","),
+        buffer_views.join(","),
+        buffer.len()
+    )
+    .into_bytes();
+    // Chunks are 4 bytes aligned, JSON is padded with spaces and binary data with zeros
+    while !json.len().is_multiple_of(4) {
+        json.push(b' ');
+    }
+    while !buffer.len().is_multiple_of(4) {
+        buffer.push(0);
+    }
+
+    let total_length = 12 + 8 + json.len() + 8 + buffer.len();
+    writer.write_all(b"glTF")?;
+    writer.write_all(&2u32.to_le_bytes())?;
+    writer.write_all(&(total_length as u32).to_le_bytes())?;
+    writer.write_all(&(json.len() as u32).to_le_bytes())?;
+    writer.write_all(b"JSON")?;
+    writer.write_all(&json)?;
+    writer.write_all(&(buffer.len() as u32).to_le_bytes())?;
+    writer.write_all(b"BIN\0")?;
+    writer.write_all(&buffer)?;
+    return Ok(());
+}
+
+impl<T: VoxelSet> VoxelWorld<T> {
+    /// Create the opaque meshes of the loaded chunks between `min` and `max` (inclusive), moved to their place in the world
+    pub fn export_chunk_meshes(&self, min: IVec2, max: IVec2) -> Vec<ExportedMesh> {
+        let mut meshes = vec![];
+        for x in min.x..=max.x {
+            for z in min.y..=max.y {
+                let pos = IVec2::new(x, z);
+                if self.get_chunk(pos).is_none() {
+                    continue;
+                }
+                let offset = Vec3::new((WIDTH as i32 * x) as f32, 0.0, (WIDTH as i32 * z) as f32);
+                meshes.push(ExportedMesh::new(&format!("chunk_{}_{}", x, z), &self.create_chunk_mesh(pos), offset));
+            }
+        }
+        return meshes;
+    }
+
+    /// Write the opaque meshes of the chunks between `min` and `max` to `path`, in the format given by its extension
+    ///
+    /// Return the number of exported chunks, chunks without opaque faces are skipped
+    pub fn export_chunks(&self, min: IVec2, max: IVec2, path: impl AsRef<Path>) -> io::Result<usize> {
+        let format = ExportFormat::from_path(&path)
+            .ok_or_else(|| io::Error::new(io::ErrorKind::InvalidInput, "unknown export format, expected .obj or .glb"))?;
+        let mut meshes = self.export_chunk_meshes(min, max);
+        meshes.retain(|mesh| !mesh.positions.is_empty());
+        let mut writer = BufWriter::new(File::create(path)?);
+        match format {
+            ExportFormat::Obj => write_obj(&meshes, &mut writer)?,
+            ExportFormat::Glb => write_glb(&meshes, &mut writer)?,
+        }
+        writer.flush()?;
+        return Ok(meshes.len());
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use std::env;
+
+    use bevy::math::{IVec2, IVec3};
+
+    use crate::{
+        chunk::WIDTH,
+        test_util::{empty_world, filled_chunk, TestSet},
+        world::VoxelWorld,
+    };
+
+    use super::{json_string, write_glb, write_obj, ExportFormat};
+
+    /// Two chunks with a single voxel each
+    fn world() -> VoxelWorld<TestSet> {
+        let mut world = empty_world([IVec2::ZERO, IVec2::new(1, 0)]);
+        world.set_voxel_id(IVec3::new(2, 3, 4), 1);
+        world.set_voxel_id(IVec3::new(WIDTH as i32 + 2, 3, 4), 1);
+        return world;
+    }
+
+    #[test]
+    fn writes_obj_with_chunk_offsets() {
+        let meshes = world().export_chunk_meshes(IVec2::new(-1, -1), IVec2::new(1, 1));
+        assert_eq!(meshes.len(), 2);
+        let mut bytes = vec![];
+        write_obj(&meshes, &mut bytes).unwrap();
+        let obj = String::from_utf8(bytes).unwrap();
+        let lines = |prefix: &str| obj.lines().filter(|line| line.starts_with(prefix)).count();
+        // 6 faces of 2 triangles per cube, vertices are duplicated
+        assert_eq!(lines("o "), 2);
+        assert_eq!(lines("v "), 2 * 36);
+        assert_eq!(lines("vn "), 2 * 36);
+        assert_eq!(lines("f "), 2 * 12);
+        assert!(obj.contains("v 18 3 4"));
+        assert!(obj.contains("f 37//37 "));
+    }
+
+    #[test]
+    fn escapes_json_names() {
+        assert_eq!(json_string("chunk_1_0"), r#""chunk_1_0""#);
+        assert_eq!(json_string("a\"b\\c\n"), r#""a\"b\\c\u000a""#);
+    }
+
+    #[test]
+    fn writes_valid_glb() {
+        let meshes = world().export_chunk_meshes(IVec2::ZERO, IVec2::new(1, 0));
+        let mut bytes = vec![];
+        write_glb(&meshes, &mut bytes).unwrap();
+        assert_eq!(&bytes[0..4], b"glTF");
+        assert_eq!(u32::from_le_bytes(bytes[8..12].try_into().unwrap()) as usize, bytes.len());
+        let json_length = u32::from_le_bytes(bytes[12..16].try_into().unwrap()) as usize;
+        assert_eq!(&bytes[16..20], b"JSON");
+        let json = String::from_utf8(bytes[20..20 + json_length].to_vec()).unwrap();
+        assert!(json.contains(r#""name":"chunk_1_0","mesh":1"#));
+        assert!(json.contains(r#""min":[18,3,4],"max":[19,4,5]"#));
+        let bin_length = u32::from_le_bytes(bytes[20 + json_length..24 + json_length].try_into().unwrap()) as usize;
+        assert_eq!(&bytes[24 + json_length..28 + json_length], b"BIN\0");
+        // Positions, normals and indices of both meshes
+        assert_eq!(bin_length, 2 * (36 * 12 * 2 + 36 * 4));
+        assert_eq!(28 + json_length + bin_length, bytes.len());
+
+        assert_eq!(ExportFormat::from_path("terrain.GLB"), Some(ExportFormat::Glb));
+        assert_eq!(ExportFormat::from_path("terrain.fbx"), None);
+    }
+
+    #[test]
+    fn counts_only_written_chunks() {
+        let mut world = world();
+        // The third chunk is empty
+        world.insert_chunk(IVec2::new(2, 0), filled_chunk(0));
+        for extension in ["obj", "glb"] {
+            let path = env::temp_dir().join(format!("voxel_engine_export.{}", extension));
+            assert_eq!(world.export_chunks(IVec2::ZERO, IVec2::new(2, 0), &path).unwrap(), 2);
+        }
+    }
+}
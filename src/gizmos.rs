@@ -0,0 +1,175 @@
+use std::{collections::HashMap, f32::consts::FRAC_PI_2, marker::PhantomData};
+
+use bevy::{
+    app::{App, Plugin, Update},
+    core_pipeline::core_3d::Camera3d,
+    ecs::{
+        event::EventReader,
+        query::With,
+        schedule::IntoSystemConfigs,
+        system::{Local, Query, Res, ResMut, Resource},
+    },
+    gizmos::gizmos::Gizmos,
+    math::{IVec2, Quat, UVec3, Vec2, Vec3},
+    render::color::Color,
+    transform::components::Transform,
+};
+
+use crate::{
+    actions::{Action, ActionState},
+    camera::{find_controlled_camera, CameraId, ControlledCamera},
+    chunk::{HEIGHT, WIDTH},
+    events::VoxelChanged,
+    meshing::remesh_dirty_chunks,
+    octree::Octree,
+    voxel::VoxelSet,
+    world::VoxelWorld,
+};
+
+/// What `ChunkGizmoPlugin` draws, can be changed at runtime
+#[derive(Debug, Clone, Resource)]
+pub struct ChunkGizmoSettings {
+    /// Draw the borders of the loaded chunks, toggled with `Action::ToggleChunkBorders`
+    pub chunk_borders: bool,
+    /// Borders are drawn for chunks at most this distance (in chunks) from the camera
+    pub border_distance: u32,
+    /// Chunk whose octree subdivision is drawn, set to the camera chunk with `Action::ToggleChunkOctree`
+    pub octree_chunk: Option<IVec2>,
+    pub border_color: Color,
+    /// Color of the chunk the camera is in
+    pub current_chunk_color: Color,
+    pub octree_color: Color,
+}
+
+/// Draw chunk borders and octrees of the `VoxelWorld<T>` with gizmos
+pub struct ChunkGizmoPlugin<T: VoxelSet> {
+    settings: ChunkGizmoSettings,
+    _voxel_set: PhantomData<T>,
+}
+
+impl<T: VoxelSet> Plugin for ChunkGizmoPlugin<T> {
+    fn build(&self, app: &mut App) {
+        // Dirty chunks are read before `remesh_dirty_chunks` takes them
+        app.insert_resource(self.settings.clone())
+            .add_event::<VoxelChanged<T>>()
+            .add_systems(Update, (toggle_chunk_gizmos, draw_chunk_gizmos::<T>).chain().before(remesh_dirty_chunks::<T>));
+    }
+}
+
+impl<T: VoxelSet> ChunkGizmoPlugin<T> {
+    pub fn new() -> Self {
+        Self {
+            settings: ChunkGizmoSettings {
+                chunk_borders: false,
+                border_distance: 4,
+                octree_chunk: None,
+                border_color: Color::GRAY,
+                current_chunk_color: Color::YELLOW,
+                octree_color: Color::CYAN,
+            },
+            _voxel_set: PhantomData,
+        }
+    }
+
+    pub fn with_chunk_borders(mut self, chunk_borders: bool) -> Self {
+        self.settings.chunk_borders = chunk_borders;
+        return self;
+    }
+    pub fn with_border_distance(mut self, border_distance: u32) -> Self {
+        self.settings.border_distance = border_distance;
+        return self;
+    }
+    pub fn with_octree_chunk(mut self, octree_chunk: Option<IVec2>) -> Self {
+        self.settings.octree_chunk = octree_chunk;
+        return self;
+    }
+}
+
+impl<T: VoxelSet> Default for ChunkGizmoPlugin<T> {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+/// Return the chunk containing the controlled camera, see `find_controlled_camera`
+fn camera_chunk(controlled_camera: &Option<Res<ControlledCamera>>, q_cameras: &Query<(&Transform, Option<&CameraId>), With<Camera3d>>) -> Option<IVec2> {
+    let camera_transform = find_controlled_camera(controlled_camera.as_deref(), q_cameras.iter())?;
+    let translation = camera_transform.translation;
+    return Some(IVec2::new((translation.x / WIDTH as f32).floor() as i32, (translation.z / WIDTH as f32).floor() as i32));
+}
+
+fn toggle_chunk_gizmos(
+    actions: Res<ActionState>,
+    controlled_camera: Option<Res<ControlledCamera>>,
+    q_cameras: Query<(&Transform, Option<&CameraId>), With<Camera3d>>,
+    mut settings: ResMut<ChunkGizmoSettings>,
+) {
+    if actions.just_pressed(Action::ToggleChunkBorders) {
+        settings.chunk_borders = !settings.chunk_borders;
+    }
+    if actions.just_pressed(Action::ToggleChunkOctree) {
+        settings.octree_chunk = match settings.octree_chunk {
+            Some(_) => None,
+            None => camera_chunk(&controlled_camera, &q_cameras),
+        };
+    }
+}
+
+/// Octrees are cached per chunk and only rebuilt when a voxel of the chunk changes or the chunk is dirty
+fn draw_chunk_gizmos<T: VoxelSet>(
+    mut gizmos: Gizmos,
+    settings: Res<ChunkGizmoSettings>,
+    world: Res<VoxelWorld<T>>,
+    controlled_camera: Option<Res<ControlledCamera>>,
+    q_cameras: Query<(&Transform, Option<&CameraId>), With<Camera3d>>,
+    mut voxel_changed_events: EventReader<VoxelChanged<T>>,
+    mut octree_cache: Local<HashMap<IVec2, Vec<Octree<T::Id>>>>,
+) {
+    for event in voxel_changed_events.read() {
+        octree_cache.remove(&IVec2::new(event.pos.x.div_euclid(WIDTH as i32), event.pos.z.div_euclid(WIDTH as i32)));
+    }
+    octree_cache.retain(|pos, _| world.get_chunk(*pos).is_some() && !world.is_chunk_dirty(*pos));
+
+    let chunk_size = Vec3::new(WIDTH as f32, HEIGHT as f32, WIDTH as f32);
+    if settings.chunk_borders {
+        let current_chunk = camera_chunk(&controlled_camera, &q_cameras);
+        for pos in world.chunk_positions() {
+            if let Some(current_chunk) = current_chunk {
+                if (pos - current_chunk).abs().max_element() as u32 > settings.border_distance {
+                    continue;
+                }
+            }
+            let min = Vec3::new((pos.x * WIDTH as i32) as f32, 0.0, (pos.y * WIDTH as i32) as f32);
+            if Some(pos) == current_chunk {
+                // Also mark the cubes the chunk is split into
+                for section in 0..=HEIGHT / WIDTH {
+                    let y = (section * WIDTH) as f32;
+                    gizmos.rect(
+                        min + Vec3::new(WIDTH as f32 / 2.0, y, WIDTH as f32 / 2.0),
+                        Quat::from_rotation_x(FRAC_PI_2),
+                        Vec2::splat(WIDTH as f32),
+                        settings.current_chunk_color,
+                    );
+                }
+                gizmos.cuboid(Transform::from_translation(min + chunk_size / 2.0).with_scale(chunk_size), settings.current_chunk_color);
+            } else {
+                gizmos.cuboid(Transform::from_translation(min + chunk_size / 2.0).with_scale(chunk_size), settings.border_color);
+            }
+        }
+    }
+
+    let Some(octree_chunk) = settings.octree_chunk else {
+        return;
+    };
+    let Some(chunk) = world.get_chunk(octree_chunk) else {
+        return;
+    };
+    let chunk_min = Vec3::new((octree_chunk.x * WIDTH as i32) as f32, 0.0, (octree_chunk.y * WIDTH as i32) as f32);
+    let octrees = octree_cache.entry(octree_chunk).or_insert_with(|| chunk.octrees());
+    for (section, octree) in octrees.iter().enumerate() {
+        for (min, size) in octree.leaf_bounds(UVec3::new(0, (section * WIDTH) as u32, 0), WIDTH as u32) {
+            let size = Vec3::splat(size as f32);
+            gizmos.cuboid(Transform::from_translation(chunk_min + min.as_vec3() + size / 2.0).with_scale(size), settings.octree_color);
+        }
+    }
+}
@@ -0,0 +1,389 @@
+use std::{fs, io, path::Path};
+
+use bevy::math::{IVec2, UVec2};
+
+use crate::{
+    chunk::{Chunk, HEIGHT, WIDTH},
+    voxel::{ColorTable, VoxelSet},
+    world::VoxelWorld,
+};
+
+fn invalid_data(message: impl ToString) -> io::Error {
+    return io::Error::new(io::ErrorKind::InvalidData, message.to_string());
+}
+
+/// Decoded image, `channels` samples from 0 to `max` per pixel, rows from the top
+struct RawImage {
+    width: u32,
+    height: u32,
+    channels: usize,
+    max: u16,
+    samples: Vec<u16>,
+}
+
+impl RawImage {
+    /// Decode a PNG or a Netpbm (PGM, PPM) image
+    fn read(bytes: &[u8]) -> io::Result<Self> {
+        if bytes.starts_with(b"\x89PNG") {
+            return Self::read_png(bytes);
+        }
+        return Self::read_netpbm(bytes);
+    }
+
+    fn read_png(bytes: &[u8]) -> io::Result<Self> {
+        let mut decoder = png::Decoder::new(bytes);
+        // Palettes and low bit depths become 8 bit samples, 16 bit samples are kept for precise heights
+        decoder.set_transformations(png::Transformations::EXPAND);
+        let mut reader = decoder.read_info().map_err(invalid_data)?;
+        let mut buffer = vec![0; reader.output_buffer_size()];
+        let info = reader.next_frame(&mut buffer).map_err(invalid_data)?;
+        let (color_type, bit_depth) = reader.output_color_type();
+        let data = &buffer[..info.buffer_size()];
+        let (max, samples) = match bit_depth {
+            png::BitDepth::Sixteen => (u16::MAX, data.chunks_exact(2).map(|sample| u16::from_be_bytes([sample[0], sample[1]])).collect()),
+            _ => (u8::MAX as u16, data.iter().map(|sample| *sample as u16).collect()),
+        };
+        return Ok(Self {
+            width: info.width,
+            height: info.height,
+            channels: color_type.samples(),
+            max,
+            samples,
+        });
+    }
+
+    /// Decode the ASCII (P2, P3) and binary (P5, P6) greyscale and color formats
+    fn read_netpbm(bytes: &[u8]) -> io::Result<Self> {
+        let (channels, binary) = match bytes.get(0..2) {
+            Some(b"P2") => (1, false),
+            Some(b"P3") => (3, false),
+            Some(b"P5") => (1, true),
+            Some(b"P6") => (3, true),
+            _ => return Err(invalid_data("unsupported image format, expected PNG, PGM or PPM")),
+        };
+        // Header fields are separated by whitespace, comments start with # and end with the line
+        let mut offset = 2;
+        let next_number = |offset: &mut usize| -> io::Result<u32> {
+            loop {
+                match bytes.get(*offset) {
+                    Some(b'#') => {
+                        while bytes.get(*offset).is_some_and(|byte| *byte != b'\n') {
+                            *offset += 1;
+                        }
+                    }
+                    Some(byte) if byte.is_ascii_whitespace() => *offset += 1,
+                    Some(_) => break,
+                    None => return Err(invalid_data("unexpected end of image")),
+                }
+            }
+            let start = *offset;
+            while bytes.get(*offset).is_some_and(|byte| byte.is_ascii_digit()) {
+                *offset += 1;
+            }
+            return std::str::from_utf8(&bytes[start..*offset])
+                .ok()
+                .and_then(|number| number.parse().ok())
+                .ok_or_else(|| invalid_data("invalid number in image"));
+        };
+        let width = next_number(&mut offset)?;
+        let height = next_number(&mut offset)?;
+        let max = next_number(&mut offset)?;
+        if max == 0 || max > u16::MAX as u32 {
+            return Err(invalid_data("invalid maximum value"));
+        }
+        let count = width as usize * height as usize * channels;
+        let samples = if binary {
+            // A single whitespace separates the header from the samples, stored on 2 bytes above 255
+            let data = bytes.get(offset + 1..).unwrap_or(&[]);
+            if max > u8::MAX as u32 {
+                data.chunks_exact(2).take(count).map(|sample| u16::from_be_bytes([sample[0], sample[1]])).collect::<Vec<_>>()
+            } else {
+                data.iter().take(count).map(|sample| *sample as u16).collect()
+            }
+        } else {
+            (0..count).map(|_| next_number(&mut offset).map(|sample| sample as u16)).collect::<io::Result<Vec<_>>>()?
+        };
+        if samples.len() != count {
+            return Err(invalid_data("image data too short"));
+        }
+        return Ok(Self {
+            width,
+            height,
+            channels,
+            max: max as u16,
+            samples,
+        });
+    }
+
+    /// Return the samples of the pixel at (`x`, `y`) scaled to 0..=1
+    fn pixel(&self, x: u32, y: u32) -> impl Iterator<Item = f32> + '_ {
+        let start = (y as usize * self.width as usize + x as usize) * self.channels;
+        return self.samples[start..start + self.channels].iter().map(|sample| *sample as f32 / self.max as f32);
+    }
+}
+
+/// Greyscale image used as terrain heights, X goes along the columns and Z along the rows
+#[derive(Debug, Clone, PartialEq)]
+pub struct Heightmap {
+    pub size: UVec2,
+    /// Height of each pixel from 0 to 1, row by row
+    pub heights: Vec<f32>,
+}
+
+impl Heightmap {
+    /// Decode a PNG or PGM image, the height of colored pixels is the average of their red, green and blue values
+    pub fn read(bytes: &[u8]) -> io::Result<Self> {
+        let image = RawImage::read(bytes)?;
+        // Alpha is ignored
+        let color_channels = if image.channels >= 3 { 3 } else { 1 };
+        let mut heights = Vec::with_capacity(image.width as usize * image.height as usize);
+        for y in 0..image.height {
+            for x in 0..image.width {
+                heights.push(image.pixel(x, y).take(color_channels).sum::<f32>() / color_channels as f32);
+            }
+        }
+        return Ok(Self {
+            size: UVec2::new(image.width, image.height),
+            heights,
+        });
+    }
+
+    pub fn load(path: impl AsRef<Path>) -> io::Result<Self> {
+        return Self::read(&fs::read(path)?);
+    }
+
+    /// Return the height at (`x`, `z`) from 0 to 1, `None` outside of the image
+    pub fn get_height(&self, x: u32, z: u32) -> Option<f32> {
+        if x >= self.size.x || z >= self.size.y {
+            return None;
+        }
+        return Some(self.heights[(z * self.size.x + x) as usize]);
+    }
+}
+
+/// Color image giving the surface voxel of each column of a `Heightmap`
+#[derive(Debug, Clone, PartialEq)]
+pub struct ColorMap {
+    pub size: UVec2,
+    /// RGBA color of each pixel, row by row
+    pub colors: Vec<[u8; 4]>,
+}
+
+impl ColorMap {
+    /// Decode a PNG or PPM image, greyscale images are accepted too
+    pub fn read(bytes: &[u8]) -> io::Result<Self> {
+        let image = RawImage::read(bytes)?;
+        let mut colors = Vec::with_capacity(image.width as usize * image.height as usize);
+        for y in 0..image.height {
+            for x in 0..image.width {
+                let samples = image.pixel(x, y).map(|sample| (sample * 255.0).round() as u8).collect::<Vec<_>>();
+                colors.push(match samples.len() {
+                    1 => [samples[0], samples[0], samples[0], 255],
+                    2 => [samples[0], samples[0], samples[0], samples[1]],
+                    3 => [samples[0], samples[1], samples[2], 255],
+                    _ => [samples[0], samples[1], samples[2], samples[3]],
+                });
+            }
+        }
+        return Ok(Self {
+            size: UVec2::new(image.width, image.height),
+            colors,
+        });
+    }
+
+    pub fn load(path: impl AsRef<Path>) -> io::Result<Self> {
+        return Self::read(&fs::read(path)?);
+    }
+
+    /// Return the color at (`x`, `z`), `None` outside of the image
+    pub fn get_color(&self, x: u32, z: u32) -> Option<[u8; 4]> {
+        if x >= self.size.x || z >= self.size.y {
+            return None;
+        }
+        return Some(self.colors[(z * self.size.x + x) as usize]);
+    }
+}
+
+/// Build the chunks of a `VoxelWorld<T>` from a heightmap
+///
+/// Each pixel is a column whose height is scaled to `0..=max_height` voxels, topped by the surface voxel (from the color map if any)
+/// and filled below with the fill voxel. Chunks on the edge of images whose size isn't a multiple of `WIDTH` are left empty past the image
+pub struct HeightmapGenerator<T: VoxelSet> {
+    heightmap: Heightmap,
+    color_map: Option<(ColorMap, ColorTable<T>)>,
+    surface_id: T::Id,
+    fill_id: T::Id,
+    max_height: u32,
+    /// Chunk holding the top left pixel
+    origin: IVec2,
+}
+
+impl<T: VoxelSet> HeightmapGenerator<T> {
+    pub fn new(heightmap: Heightmap, voxel_id: T::Id) -> Self {
+        Self {
+            heightmap,
+            color_map: None,
+            surface_id: voxel_id,
+            fill_id: voxel_id,
+            max_height: HEIGHT as u32,
+            origin: IVec2::ZERO,
+        }
+    }
+
+    /// Voxel under the surface of every column
+    pub fn with_fill_id(mut self, fill_id: T::Id) -> Self {
+        self.fill_id = fill_id;
+        return self;
+    }
+
+    /// Pick the surface voxel of each column from the closest color of `colors`, columns outside of the color map keep the default surface voxel
+    pub fn with_color_map(mut self, color_map: ColorMap, colors: ColorTable<T>) -> Self {
+        self.color_map = Some((color_map, colors));
+        return self;
+    }
+
+    /// Height of white pixels, clamped to `HEIGHT`
+    pub fn with_max_height(mut self, max_height: u32) -> Self {
+        self.max_height = max_height.min(HEIGHT as u32);
+        return self;
+    }
+
+    pub fn with_origin(mut self, origin: IVec2) -> Self {
+        self.origin = origin;
+        return self;
+    }
+
+    /// Number of chunks covered by the heightmap on X and Z
+    pub fn chunk_count(&self) -> UVec2 {
+        return (self.heightmap.size + UVec2::splat(WIDTH as u32 - 1)) / WIDTH as u32;
+    }
+
+    /// Return the number of voxels of the column at (`x`, `z`) in the heightmap
+    pub fn column_height(&self, x: u32, z: u32) -> u32 {
+        return self
+            .heightmap
+            .get_height(x, z)
+            .map_or(0, |height| (height.clamp(0.0, 1.0) * self.max_height as f32).round() as u32);
+    }
+
+    /// Build the chunk `pos` of the world, `None` if the heightmap doesn't cover it
+    pub fn generate_chunk(&self, pos: IVec2) -> Option<Chunk<T>> {
+        let chunk = pos - self.origin;
+        if chunk.cmplt(IVec2::ZERO).any() || chunk.as_uvec2().cmpge(self.chunk_count()).any() {
+            return None;
+        }
+        let mut voxels = [[[T::get_default_voxel_id(); WIDTH]; HEIGHT]; WIDTH];
+        for (x, column) in voxels.iter_mut().enumerate() {
+            for z in 0..WIDTH {
+                let (image_x, image_z) = (chunk.x as u32 * WIDTH as u32 + x as u32, chunk.y as u32 * WIDTH as u32 + z as u32);
+                let height = self.column_height(image_x, image_z) as usize;
+                if height == 0 {
+                    continue;
+                }
+                let surface_id = match &self.color_map {
+                    Some((color_map, colors)) => color_map
+                        .get_color(image_x, image_z)
+                        .and_then(|color| colors.get_voxel_id(color))
+                        .unwrap_or(self.surface_id),
+                    None => self.surface_id,
+                };
+                for row in column.iter_mut().take(height - 1) {
+                    row[z] = self.fill_id;
+                }
+                column[height - 1][z] = surface_id;
+            }
+        }
+        return Some(Chunk::new(voxels));
+    }
+
+    /// Insert every chunk covered by the heightmap into `world`, returning their coordinates
+    pub fn fill_world(&self, world: &mut VoxelWorld<T>) -> Vec<IVec2> {
+        let count = self.chunk_count();
+        let mut positions = vec![];
+        for x in 0..count.x as i32 {
+            for z in 0..count.y as i32 {
+                let pos = self.origin + IVec2::new(x, z);
+                if let Some(chunk) = self.generate_chunk(pos) {
+                    world.insert_chunk(pos, chunk);
+                    positions.push(pos);
+                }
+            }
+        }
+        return positions;
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use bevy::math::{IVec2, IVec3, UVec2};
+
+    use crate::{
+        test_util::TestSet,
+        voxel::ColorTable,
+        world::VoxelWorld,
+    };
+
+    use super::{ColorMap, Heightmap, HeightmapGenerator};
+
+    fn png(width: u32, height: u32, color_type: png::ColorType, bit_depth: png::BitDepth, data: &[u8]) -> Vec<u8> {
+        let mut bytes = vec![];
+        let mut encoder = png::Encoder::new(&mut bytes, width, height);
+        encoder.set_color(color_type);
+        encoder.set_depth(bit_depth);
+        encoder.write_header().unwrap().write_image_data(data).unwrap();
+        return bytes;
+    }
+
+    #[test]
+    fn reads_pgm_and_png() {
+        let ascii = Heightmap::read(b"P2\n# comment\n3 1\n4\n0 2 4\n").unwrap();
+        assert_eq!(ascii.size, UVec2::new(3, 1));
+        assert_eq!(ascii.heights, vec![0.0, 0.5, 1.0]);
+        let binary = Heightmap::read(b"P5 3 1 255\n\x00\x33\xff").unwrap();
+        assert!((binary.get_height(1, 0).unwrap() - 0.2).abs() < 1e-6);
+        assert_eq!(binary.get_height(3, 0), None);
+        assert!(Heightmap::read(b"P5 3 1 255\n\x00").is_err());
+        assert!(Heightmap::read(b"GIF89a").is_err());
+
+        let grey = Heightmap::read(&png(2, 1, png::ColorType::Grayscale, png::BitDepth::Sixteen, &[0, 0, 0xff, 0xff])).unwrap();
+        assert_eq!(grey.heights, vec![0.0, 1.0]);
+        let rgb = Heightmap::read(&png(1, 1, png::ColorType::Rgb, png::BitDepth::Eight, &[255, 0, 0])).unwrap();
+        assert!((rgb.heights[0] - 1.0 / 3.0).abs() < 1e-6);
+
+        let colors = ColorMap::read(b"P3 1 2 255 255 0 0 0 0 255").unwrap();
+        assert_eq!(colors.get_color(0, 1), Some([0, 0, 255, 255]));
+        let colors = ColorMap::read(&png(1, 1, png::ColorType::Rgba, png::BitDepth::Eight, &[1, 2, 3, 4])).unwrap();
+        assert_eq!(colors.colors, vec![[1, 2, 3, 4]]);
+    }
+
+    #[test]
+    fn fills_chunks_of_partial_images() {
+        // 20x3 pixels, not a multiple of the chunk width
+        let mut pixels = b"P5 20 3 255\n".to_vec();
+        pixels.extend((0..60).map(|i| if i % 20 == 19 { 255 } else { 127 }));
+        let heightmap = Heightmap::read(&pixels).unwrap();
+        let mut colors = b"P6 20 3 255\n".to_vec();
+        colors.extend((0..60).flat_map(|i| if i < 20 { [250, 0, 0] } else { [0, 0, 250] }));
+        let generator = HeightmapGenerator::<TestSet>::new(heightmap, 1)
+            .with_fill_id(2)
+            .with_max_height(64)
+            .with_color_map(ColorMap::read(&colors).unwrap(), ColorTable::new().with_color([255, 0, 0, 255], 3).with_color([0, 0, 255, 255], 4))
+            .with_origin(IVec2::new(-1, 5));
+
+        let mut world = VoxelWorld::new(Default::default());
+        let positions = generator.fill_world(&mut world);
+        assert_eq!(positions, vec![IVec2::new(-1, 5), IVec2::new(0, 5)]);
+        assert!(generator.generate_chunk(IVec2::new(1, 5)).is_none());
+
+        let origin = IVec3::new(-16, 0, 80);
+        // Heights are scaled to 0..=64, the surface comes from the color map
+        assert_eq!(world.get_voxel_id(origin + IVec3::new(0, 30, 0)), 2);
+        assert_eq!(world.get_voxel_id(origin + IVec3::new(0, 31, 0)), 3);
+        assert_eq!(world.get_voxel_id(origin + IVec3::new(0, 32, 0)), 0);
+        assert_eq!(world.get_voxel_id(origin + IVec3::new(3, 31, 2)), 4);
+        assert_eq!(world.get_voxel_id(origin + IVec3::new(19, 63, 1)), 4);
+        // Past the image the columns are empty
+        assert_eq!(world.get_voxel_id(origin + IVec3::new(20, 0, 0)), 0);
+        assert_eq!(world.get_voxel_id(origin + IVec3::new(0, 0, 3)), 0);
+    }
+}
@@ -0,0 +1,367 @@
+use std::{collections::{HashMap, VecDeque}, mem::size_of};
+
+use bevy::math::{IVec2, UVec3};
+
+use crate::chunk::{HEIGHT, WIDTH};
+
+/// Default memory limit of an `EditHistory`, in bytes
+pub const DEFAULT_HISTORY_MEMORY: usize = 16 * 1024 * 1024;
+
+/// Consecutive voxels of a chunk, in x, y, z order, changed from the same id to the same id
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct EditRun<Id> {
+    /// Index of the first voxel, `(x * HEIGHT + y) * WIDTH + z`
+    pub start: u32,
+    pub len: u32,
+    pub old_id: Id,
+    pub new_id: Id,
+}
+
+impl<Id> EditRun<Id> {
+    /// Return the positions in chunk coordinates of the voxels of the run
+    pub fn voxels(&self) -> impl Iterator<Item = UVec3> {
+        return (self.start..self.start + self.len).map(|index| {
+            let (x, rest) = (index / (HEIGHT * WIDTH) as u32, index % (HEIGHT * WIDTH) as u32);
+            return UVec3::new(x, rest / WIDTH as u32, rest % WIDTH as u32);
+        });
+    }
+}
+
+/// Named group of edits undone and redone together
+///
+/// Changes are stored per chunk as runs, so that large bulk edits only take a few runs per chunk
+#[derive(Debug, Clone)]
+pub struct Transaction<Id> {
+    pub name: String,
+    /// Runs of every chunk in the order they were made
+    pub chunks: HashMap<IVec2, Vec<EditRun<Id>>>,
+    run_count: usize,
+}
+
+impl<Id: Copy + PartialEq> Transaction<Id> {
+    pub fn new(name: &str) -> Self {
+        Self {
+            name: name.to_string(),
+            chunks: HashMap::new(),
+            run_count: 0,
+        }
+    }
+
+    /// Record that the voxel at `pos` (in chunk coordinates) of the chunk `chunk_pos` changed, merging it with the last run when possible
+    pub fn record(&mut self, chunk_pos: IVec2, pos: UVec3, old_id: Id, new_id: Id) {
+        let index = (pos.x * HEIGHT as u32 + pos.y) * WIDTH as u32 + pos.z;
+        let runs = self.chunks.entry(chunk_pos).or_default();
+        if let Some(run) = runs.last_mut() {
+            if run.start + run.len == index && run.old_id == old_id && run.new_id == new_id {
+                run.len += 1;
+                return;
+            }
+        }
+        runs.push(EditRun {
+            start: index,
+            len: 1,
+            old_id,
+            new_id,
+        });
+        self.run_count += 1;
+    }
+
+    pub fn is_empty(&self) -> bool {
+        return self.chunks.is_empty();
+    }
+
+    pub fn run_count(&self) -> usize {
+        return self.run_count;
+    }
+
+    /// Approximate memory used by the transaction, in bytes
+    pub fn memory(&self) -> usize {
+        return size_of::<Self>() + self.name.len() + self.chunks.len() * size_of::<(IVec2, Vec<EditRun<Id>>)>() + self.run_count() * size_of::<EditRun<Id>>();
+    }
+}
+
+/// Undo and redo stacks of the transactions of a `VoxelWorld`
+///
+/// The oldest transactions are dropped when the history takes more than `max_memory` bytes, undone transactions after the undo stack is empty.
+/// Edits that can't be undone, made outside of a transaction or in one larger than `max_memory`, clear the whole history
+/// since undoing the transactions before them would overwrite them
+#[derive(Debug, Clone)]
+pub struct EditHistory<Id> {
+    /// Transaction being recorded, edits made outside of a transaction aren't recorded
+    current: Option<Transaction<Id>>,
+    /// Whether the current transaction went over `max_memory`, its edits aren't recorded anymore
+    overflowed: bool,
+    undo: VecDeque<Transaction<Id>>,
+    redo: Vec<Transaction<Id>>,
+    max_memory: usize,
+    memory: usize,
+}
+
+impl<Id: Copy + PartialEq> EditHistory<Id> {
+    pub fn new(max_memory: usize) -> Self {
+        Self {
+            current: None,
+            overflowed: false,
+            undo: VecDeque::new(),
+            redo: vec![],
+            max_memory,
+            memory: 0,
+        }
+    }
+
+    /// Start recording the edits in a transaction named `name`, the open transaction is committed first
+    pub fn begin(&mut self, name: &str) {
+        self.commit();
+        self.current = Some(Transaction::new(name));
+    }
+
+    /// Transaction being recorded, if any
+    pub fn current(&self) -> Option<&Transaction<Id>> {
+        return self.current.as_ref();
+    }
+
+    /// Record a voxel change in the current transaction, outside of a transaction the change can't be undone and the history is cleared
+    ///
+    /// Once the current transaction takes more than `max_memory` bytes it is emptied and the history is cleared
+    pub fn record(&mut self, chunk_pos: IVec2, pos: UVec3, old_id: Id, new_id: Id) {
+        let Some(transaction) = &mut self.current else {
+            self.clear();
+            return;
+        };
+        if self.overflowed {
+            return;
+        }
+        transaction.record(chunk_pos, pos, old_id, new_id);
+        if transaction.memory() > self.max_memory {
+            *transaction = Transaction::new(&transaction.name);
+            self.overflowed = true;
+            self.clear();
+        }
+    }
+
+    /// Move the current transaction to the undo stack if it changed anything, clearing the redo stack
+    ///
+    /// Return `false` if the transaction went over `max_memory` bytes, its edits can't be undone
+    pub fn commit(&mut self) -> bool {
+        let Some(transaction) = self.current.take() else {
+            return true;
+        };
+        if std::mem::take(&mut self.overflowed) {
+            return false;
+        }
+        if transaction.is_empty() {
+            return true;
+        }
+        self.clear_redo();
+        self.memory += transaction.memory();
+        self.undo.push_back(transaction);
+        self.enforce_memory_limit();
+        return true;
+    }
+
+    /// Drop every transaction to undo and to redo, the current transaction is kept
+    pub fn clear(&mut self) {
+        self.undo.clear();
+        self.redo.clear();
+        self.memory = 0;
+    }
+
+    fn clear_redo(&mut self) {
+        self.memory -= self.redo.drain(..).map(|transaction| transaction.memory()).sum::<usize>();
+    }
+
+    /// Take the last transaction to undo, it has to be given back with `push_redo` once undone
+    pub fn pop_undo(&mut self) -> Option<Transaction<Id>> {
+        let transaction = self.undo.pop_back()?;
+        self.memory -= transaction.memory();
+        return Some(transaction);
+    }
+
+    pub fn push_redo(&mut self, transaction: Transaction<Id>) {
+        self.memory += transaction.memory();
+        self.redo.push(transaction);
+        self.enforce_memory_limit();
+    }
+
+    /// Take the last undone transaction, it has to be given back with `push_undo` once redone
+    pub fn pop_redo(&mut self) -> Option<Transaction<Id>> {
+        let transaction = self.redo.pop()?;
+        self.memory -= transaction.memory();
+        return Some(transaction);
+    }
+
+    /// Add a redone transaction back to the undo stack, unlike `commit` the redo stack is kept
+    pub fn push_undo(&mut self, transaction: Transaction<Id>) {
+        self.memory += transaction.memory();
+        self.undo.push_back(transaction);
+        self.enforce_memory_limit();
+    }
+
+    /// Drop the oldest transactions, then the last ones to redo, until the history fits in `max_memory`
+    fn enforce_memory_limit(&mut self) {
+        while self.memory > self.max_memory {
+            let transaction = match self.undo.pop_front() {
+                Some(transaction) => transaction,
+                None if !self.redo.is_empty() => self.redo.remove(0),
+                None => break,
+            };
+            self.memory -= transaction.memory();
+        }
+    }
+
+    /// Names of the transactions that can be undone, the most recent last
+    pub fn undo_names(&self) -> Vec<&str> {
+        return self.undo.iter().map(|transaction| transaction.name.as_str()).collect();
+    }
+
+    /// Names of the transactions that can be redone, the next one last
+    pub fn redo_names(&self) -> Vec<&str> {
+        return self.redo.iter().map(|transaction| transaction.name.as_str()).collect();
+    }
+
+    /// Approximate memory used by the undo and redo stacks, in bytes
+    pub fn memory(&self) -> usize {
+        return self.memory;
+    }
+
+    pub fn max_memory(&self) -> usize {
+        return self.max_memory;
+    }
+}
+
+impl<Id: Copy + PartialEq> Default for EditHistory<Id> {
+    fn default() -> Self {
+        Self::new(DEFAULT_HISTORY_MEMORY)
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use bevy::math::{IVec2, IVec3, UVec3};
+
+    use crate::{
+        region::Region,
+        test_util::{empty_world, filled_chunk},
+    };
+
+    use super::{EditHistory, Transaction};
+
+    #[test]
+    fn merges_consecutive_voxels_into_runs() {
+        let mut transaction = Transaction::new("fill");
+        for y in 0..4 {
+            for z in 0..16 {
+                transaction.record(IVec2::ZERO, UVec3::new(2, y, z), 0u8, 1);
+            }
+        }
+        transaction.record(IVec2::ZERO, UVec3::new(2, 4, 0), 1, 2);
+        transaction.record(IVec2::ONE, UVec3::new(2, 4, 0), 0, 1);
+        assert_eq!(transaction.run_count(), 3);
+        let runs = &transaction.chunks[&IVec2::ZERO];
+        assert_eq!(runs[0].len, 64);
+        assert_eq!(runs[0].voxels().last(), Some(UVec3::new(2, 3, 15)));
+        assert_eq!(runs[1].voxels().collect::<Vec<_>>(), vec![UVec3::new(2, 4, 0)]);
+    }
+
+    #[test]
+    fn drops_oldest_transactions_over_memory_limit() {
+        let transaction = |name: &str, x: u32| {
+            let mut transaction = Transaction::new(name);
+            transaction.record(IVec2::ZERO, UVec3::new(x, 0, 0), 0u8, 1);
+            return transaction;
+        };
+        let mut history = EditHistory::new(transaction("a", 0).memory() * 2);
+        for (i, name) in ["a", "b", "c"].iter().enumerate() {
+            history.current = Some(transaction(name, i as u32));
+            history.commit();
+        }
+        assert_eq!(history.undo_names(), vec!["b", "c"]);
+        assert!(history.memory() <= history.max_memory());
+
+        let undone = history.pop_undo().unwrap();
+        history.push_redo(undone);
+        assert_eq!(history.redo_names(), vec!["c"]);
+        // A new transaction clears the redo stack
+        history.current = Some(transaction("d", 3));
+        history.commit();
+        assert_eq!(history.undo_names(), vec!["b", "d"]);
+        assert!(history.redo_names().is_empty());
+
+        // Edits outside of a transaction clear the history
+        let undone = history.pop_undo().unwrap();
+        history.push_redo(undone);
+        history.record(IVec2::ZERO, UVec3::ZERO, 1, 0);
+        assert!(history.undo_names().is_empty() && history.redo_names().is_empty());
+        assert_eq!(history.memory(), 0);
+
+        // A transaction over the limit on its own is emptied as soon as it goes over and clears the history
+        history.current = Some(transaction("e", 4));
+        history.commit();
+        history.begin("large");
+        for x in 0..4 {
+            history.record(IVec2::new(x, 0), UVec3::ZERO, 0, 1);
+        }
+        assert!(history.current().unwrap().is_empty());
+        assert!(history.undo_names().is_empty());
+        assert!(!history.commit());
+        history.begin("f");
+        history.record(IVec2::ZERO, UVec3::ZERO, 0, 1);
+        assert!(history.commit());
+        assert_eq!(history.undo_names(), vec!["f"]);
+    }
+
+    #[test]
+    fn undoes_and_redoes_world_edits() {
+        let mut world = empty_world([IVec2::ZERO, IVec2::X]);
+        world.set_voxel_id(IVec3::new(3, 3, 3), 2);
+
+        let region = Region::new_box(IVec3::new(0, 0, 0), IVec3::new(31, 63, 15));
+        world.transaction("fill", |world| world.fill_region(&region, 1));
+        world.transaction("set", |world| {
+            world.set_voxel_id(IVec3::new(3, 3, 3), 3);
+            world.set_voxel_id(IVec3::new(3, 3, 3), 4);
+        });
+        // The fill covers whole rows of both chunks
+        assert!(world.history().memory() < 1024);
+        assert_eq!(world.history().undo_names(), vec!["fill", "set"]);
+
+        assert_eq!(world.undo().as_deref(), Some("set"));
+        assert_eq!(world.get_voxel_id(IVec3::new(3, 3, 3)), 1);
+        world.take_dirty_chunks();
+        assert_eq!(world.undo().as_deref(), Some("fill"));
+        assert_eq!(world.get_voxel_id(IVec3::new(3, 3, 3)), 2);
+        assert_eq!(world.get_voxel_id(IVec3::new(20, 10, 5)), 0);
+        assert!(world.is_chunk_dirty(IVec2::ZERO) && world.is_chunk_dirty(IVec2::X));
+        assert_eq!(world.undo(), None);
+
+        assert_eq!(world.redo().as_deref(), Some("fill"));
+        assert_eq!(world.redo().as_deref(), Some("set"));
+        assert_eq!(world.get_voxel_id(IVec3::new(3, 3, 3)), 4);
+        assert_eq!(world.get_voxel_id(IVec3::new(20, 10, 5)), 1);
+        assert_eq!(world.redo(), None);
+
+        // Undoing or redoing after an untracked edit would overwrite it
+        world.undo();
+        world.set_voxel_id(IVec3::new(3, 3, 3), 5);
+        assert_eq!(world.redo(), None);
+        assert_eq!(world.undo(), None);
+        assert_eq!(world.get_voxel_id(IVec3::new(3, 3, 3)), 5);
+
+        // So does replacing a loaded chunk, loading a new one keeps the history
+        world.transaction("set", |world| world.set_voxel_id(IVec3::new(3, 3, 3), 6));
+        world.insert_chunk(IVec2::Y, filled_chunk(0));
+        assert_eq!(world.history().undo_names(), vec!["set"]);
+        world.insert_chunk(IVec2::X, filled_chunk(0));
+        assert_eq!(world.undo(), None);
+
+        let mut world = empty_world([IVec2::ZERO]).with_history_memory(256);
+        world.transaction("set", |world| world.set_voxel_id(IVec3::new(3, 3, 3), 1));
+        world.begin_transaction("checkerboard");
+        for x in 0..16 {
+            world.set_voxel_id(IVec3::new(x, 0, x % 2), 1);
+        }
+        assert!(!world.commit_transaction());
+        assert!(world.history().undo_names().is_empty());
+        assert_eq!(world.get_voxel_id(IVec3::new(15, 0, 1)), 1);
+    }
+}
@@ -0,0 +1,63 @@
+pub mod chunk;
+pub mod voxel;
+pub mod octree;
+pub mod world;
+#[cfg(feature = "render")]
+pub mod camera;
+pub mod lod;
+pub mod meshing;
+pub mod events;
+pub mod plugin;
+pub mod surface_nets;
+pub mod collision;
+pub mod region;
+pub mod history;
+pub mod schematic;
+pub mod vox;
+pub mod export;
+pub mod heightmap;
+#[cfg(feature = "render")]
+pub mod walk;
+#[cfg(feature = "render")]
+pub mod camera_path;
+#[cfg(feature = "render")]
+pub mod debug;
+#[cfg(feature = "render")]
+pub mod gizmos;
+#[cfg(feature = "render")]
+pub mod console;
+pub mod actions;
+#[cfg(test)]
+pub(crate) mod test_util;
+
+/// Types needed to build an app on top of the engine
+pub mod prelude {
+    #[cfg(feature = "render")]
+    pub use crate::{
+        camera::{CameraId, CameraMode, CameraPlugin, CameraSettings, CameraSettingsFile, ControlledCamera, CursorState, FlyState, PauseState, SpectatorState},
+        camera_path::{CameraPath, CameraPathPlugin, CameraPathState},
+        console::{ChunkGenerator, ConsoleAppExt, ConsoleCommands, ConsolePlugin, ConsoleSettings, ConsoleState},
+        debug::DebugOverlayPlugin,
+        gizmos::{ChunkGizmoPlugin, ChunkGizmoSettings},
+        lod::LodPlugin,
+        plugin::ChunkMaterials,
+        walk::{WalkController, WalkPlugin},
+    };
+    pub use crate::{
+        actions::{Action, ActionMap, ActionPlugin, ActionState, Binding},
+        collision::{Aabb, CollisionResult},
+        chunk::{Chunk, ChunkMarker, TransparentChunkMarker, HEIGHT, WIDTH},
+        events::{ChunkLoaded, ChunkMeshed, ChunkUnloaded, VoxelChanged},
+        export::{ExportFormat, ExportedMesh},
+        heightmap::{ColorMap, Heightmap, HeightmapGenerator},
+        history::{EditHistory, Transaction},
+        lod::ChunkLodMeshes,
+        octree::Octree,
+        plugin::{VoxelWorldPlugin, VoxelWorldSettings},
+        region::Region,
+        schematic::Schematic,
+        voxel::{BasicSet, ColorTable, Orientation, Voxel, VoxelSet},
+        vox::VoxFile,
+        world::{MeshingMode, VoxelWorld},
+    };
+}
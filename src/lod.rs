@@ -0,0 +1,134 @@
+use bevy::{asset::Handle, ecs::component::Component, render::mesh::Mesh};
+#[cfg(feature = "render")]
+use bevy::{
+    app::{Plugin, Update},
+    core_pipeline::core_3d::Camera3d,
+    ecs::{
+        query::{With, Without},
+        system::{Query, Res, Resource},
+    },
+    math::Vec3,
+    transform::components::Transform,
+};
+
+#[cfg(feature = "render")]
+use crate::{
+    camera::{find_controlled_camera, CameraId, ControlledCamera},
+    chunk::{ChunkMarker, WIDTH},
+};
+
+/// Number of level of details generated for a chunk, level `n` is downsampled by `2^n`
+pub const LOD_LEVELS: u32 = 4;
+
+/// Meshes of a chunk for every level of detail, index 0 being full resolution
+///
+/// Only the mesh of the level in use is generated by `remesh_dirty_chunks`, the others are generated when the level is picked.
+#[derive(Debug, Component)]
+pub struct ChunkLodMeshes {
+    /// Mesh of every level, `None` when it wasn't generated since the last change of the chunk
+    pub meshes: Vec<Option<Handle<Mesh>>>,
+    /// Level in use, picked by `LodPlugin`
+    pub level: usize,
+}
+
+impl ChunkLodMeshes {
+    /// Meshes of `levels` levels of detail, none generated yet
+    pub fn new(levels: usize) -> Self {
+        Self {
+            meshes: vec![None; levels],
+            level: 0,
+        }
+    }
+}
+
+#[cfg(feature = "render")]
+#[derive(Debug, Resource)]
+struct LodSettings {
+    lod_distance: f32,
+}
+
+/// Pick the mesh of every `ChunkMarker` entity with `ChunkLodMeshes` according to its distance to the controlled camera
+#[cfg(feature = "render")]
+pub struct LodPlugin {
+    lod_distance: f32,
+}
+
+#[cfg(feature = "render")]
+impl Plugin for LodPlugin {
+    fn build(&self, app: &mut bevy::prelude::App) {
+        app.insert_resource(LodSettings {
+            lod_distance: self.lod_distance,
+        })
+        .add_systems(Update, update_chunk_lod);
+    }
+}
+
+#[cfg(feature = "render")]
+impl LodPlugin {
+    pub fn new() -> Self {
+        Self { lod_distance: 64.0 }
+    }
+
+    /// Distance covered by each level of detail, chunks further than `LOD_LEVELS * lod_distance` use the coarsest level
+    pub fn with_lod_distance(mut self, lod_distance: f32) -> Self {
+        self.lod_distance = lod_distance;
+        return self;
+    }
+}
+
+#[cfg(feature = "render")]
+impl Default for LodPlugin {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+/// Return the level of detail to use for a chunk at `distance` from the camera
+pub fn lod_level_for_distance(distance: f32, lod_distance: f32) -> usize {
+    return ((distance / lod_distance) as usize).min(LOD_LEVELS as usize - 1);
+}
+
+/// Chunk entities whose mesh is picked by `update_chunk_lod`
+#[cfg(feature = "render")]
+type ChunkLodQuery<'w, 's> =
+    Query<'w, 's, (&'static Transform, &'static mut ChunkLodMeshes, &'static mut Handle<Mesh>), (With<ChunkMarker>, Without<Camera3d>)>;
+
+#[cfg(feature = "render")]
+fn update_chunk_lod(
+    settings: Res<LodSettings>,
+    controlled_camera: Option<Res<ControlledCamera>>,
+    q_camera: Query<(&Transform, Option<&CameraId>), With<Camera3d>>,
+    mut q_chunks: ChunkLodQuery,
+) {
+    let Some(camera_transform) = find_controlled_camera(controlled_camera.as_deref(), q_camera.iter()) else {
+        return;
+    };
+    for (chunk_transform, mut lod_meshes, mut mesh) in q_chunks.iter_mut() {
+        let chunk_center = chunk_transform.translation + Vec3::new(WIDTH as f32 / 2.0, 0.0, WIDTH as f32 / 2.0);
+        let mut offset = camera_transform.translation - chunk_center;
+        offset.y = 0.0;
+        let level = lod_level_for_distance(offset.length(), settings.lod_distance).min(lod_meshes.meshes.len() - 1);
+        if lod_meshes.level != level {
+            lod_meshes.level = level;
+        }
+        // Levels not generated yet are swapped in by `remesh_dirty_chunks`
+        if let Some(level_mesh) = &lod_meshes.meshes[level] {
+            if *mesh != *level_mesh {
+                *mesh = level_mesh.clone();
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::{lod_level_for_distance, LOD_LEVELS};
+
+    #[test]
+    fn picks_levels_by_distance() {
+        assert_eq!(lod_level_for_distance(0.0, 64.0), 0);
+        assert_eq!(lod_level_for_distance(63.9, 64.0), 0);
+        assert_eq!(lod_level_for_distance(64.0, 64.0), 1);
+        assert_eq!(lod_level_for_distance(10_000.0, 64.0), LOD_LEVELS as usize - 1);
+    }
+}
@@ -0,0 +1,93 @@
+use std::collections::HashSet;
+
+use bevy::{
+    asset::{Assets, Handle},
+    ecs::{
+        change_detection::DetectChangesMut,
+        entity::Entity,
+        event::{EventReader, EventWriter},
+        query::{With, Without},
+        system::{Commands, Query, ResMut},
+    },
+    hierarchy::{Children, DespawnRecursiveExt},
+    math::IVec2,
+    render::mesh::Mesh,
+};
+
+use crate::{
+    chunk::{ChunkMarker, TransparentChunkMarker},
+    events::{ChunkMeshed, ChunkUnloaded},
+    lod::ChunkLodMeshes,
+    voxel::VoxelSet,
+    world::VoxelWorld,
+};
+
+/// Chunk entities whose meshes are swapped by `remesh_dirty_chunks`
+type ChunkMeshQuery<'w, 's> =
+    Query<'w, 's, (Entity, &'static ChunkMarker, &'static mut Handle<Mesh>, Option<&'static mut ChunkLodMeshes>, Option<&'static Children>)>;
+
+/// Remesh the chunks marked dirty in the `VoxelWorld` resource and swap the meshes of their entities
+///
+/// Only the level of detail in use is remeshed, the other ones are generated when `LodPlugin` picks them.
+pub fn remesh_dirty_chunks<T: VoxelSet>(
+    mut world: ResMut<VoxelWorld<T>>,
+    mut meshes: ResMut<Assets<Mesh>>,
+    mut q_chunks: ChunkMeshQuery,
+    mut q_transparent: Query<&mut Handle<Mesh>, (With<TransparentChunkMarker>, Without<ChunkMarker>)>,
+    mut chunk_meshed_events: EventWriter<ChunkMeshed>,
+) {
+    let mut dirty_chunks: HashSet<IVec2> = world.bypass_change_detection().take_dirty_chunks().into_iter().collect();
+
+    for (entity, chunk_marker, mut mesh, lod_meshes, children) in q_chunks.iter_mut() {
+        let pos = chunk_marker.pos;
+        let dirty = dirty_chunks.remove(&pos);
+
+        if let Some(mut lod_meshes) = lod_meshes {
+            if dirty {
+                lod_meshes.meshes.fill(None);
+            }
+            let level = lod_meshes.level;
+            if lod_meshes.meshes[level].is_some() {
+                continue;
+            }
+            let level_mesh = meshes.add(world.create_chunk_lod_mesh(pos, level as u32));
+            lod_meshes.meshes[level] = Some(level_mesh.clone());
+            *mesh = level_mesh;
+        } else if dirty {
+            *mesh = meshes.add(world.create_chunk_mesh(pos));
+        } else {
+            continue;
+        }
+
+        // The transparent mesh has no levels of detail, it only changes with the chunk
+        if dirty {
+            for child in children.into_iter().flatten() {
+                if let Ok(mut transparent_mesh) = q_transparent.get_mut(*child) {
+                    *transparent_mesh = meshes.add(world.create_chunk_transparent_mesh(pos));
+                }
+            }
+        }
+
+        chunk_meshed_events.send(ChunkMeshed { entity, pos });
+    }
+
+    // Chunks without an entity yet stay dirty until it is spawned
+    for pos in dirty_chunks {
+        world.bypass_change_detection().mark_chunk_dirty(pos);
+    }
+}
+
+/// Despawn the entities of the chunks unloaded from the world
+pub fn despawn_unloaded_chunks(
+    mut commands: Commands,
+    mut chunk_unloaded_events: EventReader<ChunkUnloaded>,
+    q_chunks: Query<(Entity, &ChunkMarker)>,
+) {
+    for event in chunk_unloaded_events.read() {
+        for (entity, chunk_marker) in q_chunks.iter() {
+            if chunk_marker.pos == event.pos {
+                commands.entity(entity).despawn_recursive();
+            }
+        }
+    }
+}
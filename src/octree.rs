@@ -1,10 +1,12 @@
 use core::panic;
 use std::marker::PhantomData;
 
+use bevy::math::UVec3;
+
 use crate::{chunk::Chunk, voxel::VoxelSet};
 
 #[derive(Debug)]
-pub enum Octree<T: PartialEq + Clone + ToString> {
+pub enum Octree<T: PartialEq + Clone> {
     Subdivised {
         childs: [Box<Octree<T>>; 8],
     },
@@ -13,6 +15,25 @@ pub enum Octree<T: PartialEq + Clone + ToString> {
     }
 }
 
+impl<T: PartialEq + Clone + Copy> Octree<T> {
+    /// Return the minimum corner and size of every leaf for a tree covering the cube at `min` of `size` voxels
+    pub fn leaf_bounds(&self, min: UVec3, size: u32) -> Vec<(UVec3, u32)> {
+        match self {
+            Octree::Uniform { .. } => return vec![(min, size)],
+            Octree::Subdivised { childs } => {
+                let half = size / 2;
+                let mut bounds = vec![];
+                for (i, child) in childs.iter().enumerate() {
+                    // Child index bits are x, y, z from the highest
+                    let offset = UVec3::new((i as u32 >> 2) & 1, (i as u32 >> 1) & 1, i as u32 & 1) * half;
+                    bounds.extend(child.leaf_bounds(min + offset, half));
+                }
+                return bounds;
+            }
+        }
+    }
+}
+
 impl<T: PartialEq + Clone + Copy + ToString> Octree<T> {
     pub fn string_repr(&self) -> String {
         let string = String::new();
@@ -23,7 +44,7 @@ impl<T: PartialEq + Clone + Copy + ToString> Octree<T> {
     }
 }
 
-impl<T: PartialEq + Clone + Copy + ToString> From<Vec<Vec<Vec<T>>>> for Octree<T> {
+impl<T: PartialEq + Clone + Copy> From<Vec<Vec<Vec<T>>>> for Octree<T> {
     fn from(value: Vec<Vec<Vec<T>>>) -> Self {
         let lim_x = value.len();
         let lim_y = value[0].len();
@@ -101,4 +122,25 @@ impl<T: PartialEq + Clone + Copy + ToString> From<Vec<Vec<Vec<T>>>> for Octree<T
         todo!()
 
     }
-}
\ No newline at end of file
+}
+
+#[cfg(test)]
+mod test {
+    use bevy::math::UVec3;
+
+    use super::Octree;
+
+    #[test]
+    fn leaf_bounds_follow_subdivision() {
+        assert_eq!(Octree::from(vec![vec![vec![0; 4]; 4]; 4]).leaf_bounds(UVec3::ZERO, 4), vec![(UVec3::ZERO, 4)]);
+
+        let mut volume = vec![vec![vec![0; 4]; 4]; 4];
+        volume[3][0][1] = 1;
+        let bounds = Octree::from(volume).leaf_bounds(UVec3::new(0, 16, 0), 4);
+        // 7 uniform octants and the one holding the voxel split into 8
+        assert_eq!(bounds.len(), 15);
+        assert!(bounds.contains(&(UVec3::new(3, 16, 1), 1)));
+        assert!(bounds.contains(&(UVec3::new(0, 18, 2), 2)));
+        assert_eq!(bounds.iter().map(|(_, size)| size * size * size).sum::<u32>(), 64);
+    }
+}
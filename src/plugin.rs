@@ -0,0 +1,212 @@
+use std::marker::PhantomData;
+
+use bevy::{
+    app::{App, Plugin, Update},
+    asset::{AssetApp, Assets, Handle},
+    ecs::{
+        event::EventReader,
+        schedule::IntoSystemConfigs,
+        system::{Commands, Query, Resource},
+    },
+    hierarchy::BuildChildren,
+    render::{color::Color, mesh::Mesh, prelude::SpatialBundle},
+    transform::components::Transform,
+};
+#[cfg(feature = "render")]
+use bevy::{
+    app::Startup,
+    core_pipeline::core_3d::Camera3d,
+    ecs::{
+        query::{With, Without},
+        system::{Res, ResMut},
+    },
+    math::IVec2,
+    pbr::{AlphaMode, StandardMaterial},
+    render::view::Visibility,
+};
+#[cfg(feature = "render")]
+use crate::camera::{find_controlled_camera, CameraId, ControlledCamera};
+
+use crate::{
+    chunk::{ChunkMarker, TransparentChunkMarker, WIDTH},
+    events::{send_world_events, ChunkLoaded, ChunkMeshed, ChunkUnloaded, VoxelChanged},
+    lod::{ChunkLodMeshes, LOD_LEVELS},
+    meshing::{despawn_unloaded_chunks, remesh_dirty_chunks},
+    voxel::VoxelSet,
+    world::{MeshingMode, VoxelWorld},
+};
+
+/// Settings of the world subsystem
+#[derive(Debug, Resource)]
+pub struct VoxelWorldSettings {
+    /// Chunks further than this distance (in chunks) from the camera are hidden, can be changed at runtime
+    pub view_distance: u32,
+    /// Colors of the chunk materials, only read at startup
+    pub chunk_color: Color,
+    pub transparent_color: Color,
+}
+
+/// Materials shared by every chunk entity, created at startup from `VoxelWorldSettings`
+#[cfg(feature = "render")]
+#[derive(Debug, Resource)]
+pub struct ChunkMaterials {
+    pub opaque: Handle<StandardMaterial>,
+    pub transparent: Handle<StandardMaterial>,
+}
+
+/// Insert a `VoxelWorld<T>` resource and spawn, mesh and despawn the chunk entities as chunks are loaded, edited and unloaded
+///
+/// Works in headless apps (`MinimalPlugins` and `AssetPlugin`), chunk entities then only hold their meshes.
+/// Apps ticked with `App::update` instead of `App::run` must call `App::finish` first so that meshes are registered.
+pub struct VoxelWorldPlugin<T: VoxelSet> {
+    view_distance: u32,
+    chunk_color: Color,
+    transparent_color: Color,
+    meshing_mode: MeshingMode,
+    _voxel_set: PhantomData<T>,
+}
+
+impl<T: VoxelSet> Plugin for VoxelWorldPlugin<T> {
+    fn build(&self, app: &mut App) {
+        app.insert_resource(VoxelWorld::<T>::new(Default::default()).with_meshing_mode(self.meshing_mode))
+            .insert_resource(VoxelWorldSettings {
+                view_distance: self.view_distance,
+                chunk_color: self.chunk_color,
+                transparent_color: self.transparent_color,
+            })
+            .add_event::<ChunkLoaded>()
+            .add_event::<ChunkUnloaded>()
+            .add_event::<ChunkMeshed>()
+            .add_event::<VoxelChanged<T>>()
+            .add_systems(
+                Update,
+                (
+                    send_world_events::<T>,
+                    (spawn_loaded_chunks, despawn_unloaded_chunks),
+                    remesh_dirty_chunks::<T>,
+                )
+                    .chain(),
+            );
+
+        #[cfg(feature = "render")]
+        app.add_systems(Startup, create_chunk_materials)
+            .add_systems(Update, update_chunk_visibility.after(remesh_dirty_chunks::<T>));
+    }
+
+    fn finish(&self, app: &mut App) {
+        // Headless apps don't have the `RenderPlugin` registering meshes
+        if !app.world.contains_resource::<Assets<Mesh>>() {
+            app.init_asset::<Mesh>();
+        }
+    }
+}
+
+impl<T: VoxelSet> VoxelWorldPlugin<T> {
+    pub fn new() -> Self {
+        Self {
+            view_distance: 16,
+            chunk_color: Color::RED,
+            transparent_color: Color::rgba(0.2, 0.4, 1.0, 0.5),
+            meshing_mode: MeshingMode::Blocky,
+            _voxel_set: PhantomData,
+        }
+    }
+
+    pub fn with_view_distance(mut self, view_distance: u32) -> Self {
+        self.view_distance = view_distance;
+        return self;
+    }
+    pub fn with_chunk_color(mut self, chunk_color: Color) -> Self {
+        self.chunk_color = chunk_color;
+        return self;
+    }
+    pub fn with_transparent_color(mut self, transparent_color: Color) -> Self {
+        self.transparent_color = transparent_color;
+        return self;
+    }
+    pub fn with_meshing_mode(mut self, meshing_mode: MeshingMode) -> Self {
+        self.meshing_mode = meshing_mode;
+        return self;
+    }
+}
+
+impl<T: VoxelSet> Default for VoxelWorldPlugin<T> {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+#[cfg(feature = "render")]
+fn create_chunk_materials(mut commands: Commands, settings: Res<VoxelWorldSettings>, materials: Option<ResMut<Assets<StandardMaterial>>>) {
+    let Some(mut materials) = materials else {
+        return;
+    };
+    commands.insert_resource(ChunkMaterials {
+        opaque: materials.add(settings.chunk_color),
+        transparent: materials.add(StandardMaterial {
+            base_color: settings.transparent_color,
+            alpha_mode: AlphaMode::Blend,
+            ..Default::default()
+        }),
+    });
+}
+
+/// Spawn an entity for every newly loaded chunk, its meshes are generated by `remesh_dirty_chunks`
+fn spawn_loaded_chunks(
+    mut commands: Commands,
+    mut chunk_loaded_events: EventReader<ChunkLoaded>,
+    #[cfg(feature = "render")] chunk_materials: Option<Res<ChunkMaterials>>,
+    q_chunks: Query<&ChunkMarker>,
+) {
+    for event in chunk_loaded_events.read() {
+        if q_chunks.iter().any(|chunk_marker| chunk_marker.pos == event.pos) {
+            continue;
+        }
+        let chunk = commands
+            .spawn((
+                SpatialBundle::from_transform(Transform::from_xyz((WIDTH as i32 * event.pos.x) as f32, 0.0, (WIDTH as i32 * event.pos.y) as f32)),
+                Handle::<Mesh>::default(),
+                ChunkMarker { pos: event.pos },
+                ChunkLodMeshes::new(LOD_LEVELS as usize),
+            ))
+            .id();
+        let transparent_chunk = commands
+            .spawn((SpatialBundle::default(), Handle::<Mesh>::default(), TransparentChunkMarker))
+            .id();
+        commands.entity(chunk).add_child(transparent_chunk);
+
+        #[cfg(feature = "render")]
+        if let Some(chunk_materials) = &chunk_materials {
+            commands.entity(chunk).insert(chunk_materials.opaque.clone());
+            commands.entity(transparent_chunk).insert(chunk_materials.transparent.clone());
+        }
+    }
+}
+
+/// Hide the chunks further than `VoxelWorldSettings::view_distance` from the controlled camera
+#[cfg(feature = "render")]
+fn update_chunk_visibility(
+    settings: Res<VoxelWorldSettings>,
+    controlled_camera: Option<Res<ControlledCamera>>,
+    q_camera: Query<(&Transform, Option<&CameraId>), With<Camera3d>>,
+    mut q_chunks: Query<(&ChunkMarker, &mut Visibility), Without<Camera3d>>,
+) {
+    let Some(camera_transform) = find_controlled_camera(controlled_camera.as_deref(), q_camera.iter()) else {
+        return;
+    };
+    let camera_chunk = IVec2::new(
+        (camera_transform.translation.x / WIDTH as f32).floor() as i32,
+        (camera_transform.translation.z / WIDTH as f32).floor() as i32,
+    );
+    for (chunk_marker, mut visibility) in q_chunks.iter_mut() {
+        let offset = (chunk_marker.pos - camera_chunk).abs();
+        let new_visibility = if offset.max_element() as u32 <= settings.view_distance {
+            Visibility::Inherited
+        } else {
+            Visibility::Hidden
+        };
+        if *visibility != new_visibility {
+            *visibility = new_visibility;
+        }
+    }
+}
@@ -0,0 +1,163 @@
+use std::collections::HashSet;
+
+use bevy::math::{IVec2, IVec3};
+
+use crate::{voxel::VoxelSet, world::VoxelWorld};
+
+/// Set of voxels edited by the bulk operations of `VoxelWorld`
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum Region {
+    /// Voxels between two corners, inclusive
+    Box { min: IVec3, max: IVec3 },
+    /// Voxels whose center is at most `radius` from the center of the voxel `center`
+    Sphere { center: IVec3, radius: f32 },
+    /// Vertical cylinder of `height` voxels, `base` is the center voxel of its bottom layer
+    Cylinder { base: IVec3, radius: f32, height: u32 },
+}
+
+impl Region {
+    /// Create a box from any two opposite corners
+    pub fn new_box(corner: IVec3, opposite_corner: IVec3) -> Self {
+        return Region::Box {
+            min: corner.min(opposite_corner),
+            max: corner.max(opposite_corner),
+        };
+    }
+
+    /// Return the minimum and maximum voxels of the region bounding box, inclusive
+    pub fn bounds(&self) -> (IVec3, IVec3) {
+        match *self {
+            Region::Box { min, max } => return (min, max),
+            Region::Sphere { center, radius } => {
+                let extent = IVec3::splat(radius.max(0.0).floor() as i32);
+                return (center - extent, center + extent);
+            }
+            Region::Cylinder { base, radius, height } => {
+                let extent = radius.max(0.0).floor() as i32;
+                return (
+                    base - IVec3::new(extent, 0, extent),
+                    base + IVec3::new(extent, height as i32 - 1, extent),
+                );
+            }
+        }
+    }
+
+    pub fn contains(&self, pos: IVec3) -> bool {
+        match *self {
+            Region::Box { min, max } => return pos.cmpge(min).all() && pos.cmple(max).all(),
+            Region::Sphere { center, radius } => return (pos - center).as_vec3().length_squared() <= radius * radius,
+            Region::Cylinder { base, radius, height } => {
+                let offset = pos - base;
+                return offset.y >= 0 && offset.y < height as i32 && ((offset.x * offset.x + offset.z * offset.z) as f32) <= radius * radius;
+            }
+        }
+    }
+
+    /// Whether `pos` is in the region and one of its face neighbours isn't
+    pub fn is_on_surface(&self, pos: IVec3) -> bool {
+        if !self.contains(pos) {
+            return false;
+        }
+        return [IVec3::X, IVec3::NEG_X, IVec3::Y, IVec3::NEG_Y, IVec3::Z, IVec3::NEG_Z]
+            .iter()
+            .any(|offset| !self.contains(pos + *offset));
+    }
+}
+
+impl<T: VoxelSet> VoxelWorld<T> {
+    /// Set every voxel of `region` to `voxel_id`, returning the modified chunks
+    pub fn fill_region(&mut self, region: &Region, voxel_id: T::Id) -> HashSet<IVec2> {
+        let (min, max) = region.bounds();
+        return self.edit_voxels(min, max, |pos, _| region.contains(pos).then_some(voxel_id));
+    }
+
+    /// Replace the voxels of `region` with id `from` by `to`, returning the modified chunks
+    pub fn replace_in_region(&mut self, region: &Region, from: T::Id, to: T::Id) -> HashSet<IVec2> {
+        let (min, max) = region.bounds();
+        return self.edit_voxels(min, max, |pos, voxel_id| (voxel_id == from && region.contains(pos)).then_some(to));
+    }
+
+    /// Clear the inside of `region` with the default voxel, keeping its surface, returning the modified chunks
+    pub fn hollow_region(&mut self, region: &Region) -> HashSet<IVec2> {
+        let (min, max) = region.bounds();
+        return self.edit_voxels(min, max, |pos, _| {
+            (region.contains(pos) && !region.is_on_surface(pos)).then(T::get_default_voxel_id)
+        });
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use std::collections::HashSet;
+
+    use bevy::math::{IVec2, IVec3};
+
+    use crate::{
+        test_util::{empty_world, TestSet},
+        world::VoxelWorld,
+    };
+
+    use super::Region;
+
+    /// 2x2 empty chunks around the origin
+    fn world() -> VoxelWorld<TestSet> {
+        return empty_world((-1..1).flat_map(|x| (-1..1).map(move |z| IVec2::new(x, z))));
+    }
+
+    fn count(world: &VoxelWorld<TestSet>, region: &Region, voxel_id: u8) -> usize {
+        let (min, max) = region.bounds();
+        let mut count = 0;
+        for x in min.x..=max.x {
+            for y in min.y..=max.y {
+                for z in min.z..=max.z {
+                    if world.get_voxel_id(IVec3::new(x, y, z)) == voxel_id {
+                        count += 1;
+                    }
+                }
+            }
+        }
+        return count;
+    }
+
+    #[test]
+    fn fills_regions_across_chunks() {
+        let mut world = world();
+        let region = Region::new_box(IVec3::new(2, 5, 3), IVec3::new(-3, 1, -2));
+        let modified = world.fill_region(&region, 1);
+        assert_eq!(modified, HashSet::from([IVec2::new(0, 0), IVec2::new(-1, 0), IVec2::new(0, -1), IVec2::new(-1, -1)]));
+        assert_eq!(count(&world, &region, 1), 6 * 5 * 6);
+        assert_eq!(world.take_voxel_changes().len(), 6 * 5 * 6);
+        assert_eq!(world.dirty_chunk_count(), 4);
+        // Nothing changes the second time
+        assert!(world.fill_region(&region, 1).is_empty());
+
+        let sphere = Region::Sphere { center: IVec3::new(8, 20, 8), radius: 2.0 };
+        assert_eq!(world.fill_region(&sphere, 1), HashSet::from([IVec2::new(0, 0)]));
+        // Center, 6 at distance 1 and 2, 12 at distance sqrt 2 and 8 at distance sqrt 3
+        assert_eq!(count(&world, &sphere, 1), 1 + 6 + 6 + 12 + 8);
+
+        let cylinder = Region::Cylinder { base: IVec3::new(8, 30, 8), radius: 1.0, height: 3 };
+        world.fill_region(&cylinder, 1);
+        assert_eq!(count(&world, &cylinder, 1), 5 * 3);
+    }
+
+    #[test]
+    fn replaces_and_hollows_regions() {
+        let mut world = world();
+        let region = Region::new_box(IVec3::new(0, 0, 0), IVec3::new(4, 4, 4));
+        world.fill_region(&region, 1);
+        world.fill_region(&Region::new_box(IVec3::new(0, 0, 0), IVec3::new(0, 4, 4)), 2);
+
+        world.replace_in_region(&region, 2, 3);
+        assert_eq!(count(&world, &region, 3), 25);
+        assert_eq!(count(&world, &region, 2), 0);
+
+        world.take_dirty_chunks();
+        assert_eq!(world.hollow_region(&region), HashSet::from([IVec2::new(0, 0)]));
+        assert_eq!(count(&world, &region, 0), 3 * 3 * 3);
+        assert_eq!(world.get_voxel_id(IVec3::new(2, 2, 2)), 0);
+        assert_eq!(world.get_voxel_id(IVec3::new(2, 4, 2)), 1);
+        // The inside isn't on a chunk border, the neighbour chunks stay clean
+        assert!(world.is_chunk_dirty(IVec2::new(0, 0)) && !world.is_chunk_dirty(IVec2::new(-1, 0)));
+    }
+}
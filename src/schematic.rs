@@ -0,0 +1,270 @@
+use std::{collections::HashSet, fs, io, path::Path};
+
+use bevy::math::{IVec2, IVec3, UVec3};
+use serde::{de::DeserializeOwned, Deserialize, Serialize};
+
+use crate::{
+    region::Region,
+    voxel::{Orientation, VoxelSet},
+    world::VoxelWorld,
+};
+
+/// Voxels of a schematic file, either listed or as indices in a palette of the distinct ids
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+enum SchematicVoxels<Id> {
+    Raw(Vec<Id>),
+    Palette { palette: Vec<Id>, indices: Vec<u32> },
+}
+
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+struct SchematicFile<Id> {
+    size: UVec3,
+    voxels: SchematicVoxels<Id>,
+}
+
+/// Box of voxels copied out of a `VoxelWorld`, to be transformed and pasted back anywhere
+pub struct Schematic<T: VoxelSet> {
+    size: UVec3,
+    /// Ids in x, y, z order
+    voxels: Vec<T::Id>,
+}
+
+impl<T: VoxelSet> Clone for Schematic<T> {
+    fn clone(&self) -> Self {
+        Self {
+            size: self.size,
+            voxels: self.voxels.clone(),
+        }
+    }
+}
+
+/// Number of voxels of a schematic of `size`, `None` if it doesn't fit in `usize`
+fn voxel_count(size: UVec3) -> Option<usize> {
+    return (size.x as usize).checked_mul(size.y as usize)?.checked_mul(size.z as usize);
+}
+
+impl<T: VoxelSet> Schematic<T> {
+    /// Create a schematic of `size` voxels filled with the default voxel
+    ///
+    /// Panic if its voxel count doesn't fit in `usize`
+    pub fn new(size: UVec3) -> Self {
+        let count = voxel_count(size).expect("schematic voxel count overflows usize");
+        Self {
+            size,
+            voxels: vec![T::get_default_voxel_id(); count],
+        }
+    }
+
+    /// Copy the voxels of `region` from the world, the voxels of its bounding box outside of it are left to the default voxel
+    pub fn from_world(world: &VoxelWorld<T>, region: &Region) -> Self {
+        let (min, max) = region.bounds();
+        let mut schematic = Self::new((max - min + IVec3::ONE).max(IVec3::ZERO).as_uvec3());
+        for pos in schematic.positions() {
+            let world_pos = min + pos.as_ivec3();
+            if region.contains(world_pos) {
+                schematic.set_voxel_id(pos, world.get_voxel_id(world_pos));
+            }
+        }
+        return schematic;
+    }
+
+    pub fn size(&self) -> UVec3 {
+        return self.size;
+    }
+
+    /// Index of `pos` in `voxels`, computed in `usize` since the voxel count of a schematic always fits in it but not always in `u32`
+    fn index(&self, pos: UVec3) -> usize {
+        let (size_y, size_z) = (self.size.y as usize, self.size.z as usize);
+        return (pos.x as usize * size_y + pos.y as usize) * size_z + pos.z as usize;
+    }
+
+    /// Return every position of the schematic, in x, y, z order
+    pub fn positions(&self) -> impl Iterator<Item = UVec3> {
+        let size = self.size;
+        return (0..size.x).flat_map(move |x| (0..size.y).flat_map(move |y| (0..size.z).map(move |z| UVec3::new(x, y, z))));
+    }
+
+    pub fn get_voxel_id(&self, pos: UVec3) -> T::Id {
+        return self.voxels[self.index(pos)];
+    }
+
+    pub fn set_voxel_id(&mut self, pos: UVec3, voxel_id: T::Id) {
+        let index = self.index(pos);
+        self.voxels[index] = voxel_id;
+    }
+
+    /// Build a schematic of `size` where the voxel at each position comes from `source`, with its orientation remapped by `orient`
+    fn transformed(&self, size: UVec3, source: impl Fn(UVec3) -> UVec3, orient: impl Fn(Orientation) -> Orientation) -> Self {
+        let mut schematic = Self::new(size);
+        for pos in schematic.positions() {
+            let voxel_id = self.get_voxel_id(source(pos));
+            let voxel_id = match T::get_orientation(voxel_id) {
+                Some(orientation) => T::with_orientation(voxel_id, orient(orientation)),
+                None => voxel_id,
+            };
+            schematic.set_voxel_id(pos, voxel_id);
+        }
+        return schematic;
+    }
+
+    /// Return the schematic after `quarter_turns` rotations of 90° around Y, see `Orientation::rotated_y`
+    pub fn rotated_y(&self, quarter_turns: i32) -> Self {
+        let mut schematic = self.clone();
+        for _ in 0..quarter_turns.rem_euclid(4) {
+            // X+ goes to Z-, the voxel at (x, z) comes from (size.x - 1 - z, x)
+            let size = schematic.size;
+            schematic = schematic.transformed(
+                UVec3::new(size.z, size.y, size.x),
+                |pos| UVec3::new(size.x - 1 - pos.z, pos.y, pos.x),
+                |orientation| orientation.rotated_y(1),
+            );
+        }
+        return schematic;
+    }
+
+    /// Return the schematic mirrored along the X axis
+    pub fn mirrored_x(&self) -> Self {
+        let size = self.size;
+        return self.transformed(size, |pos| UVec3::new(size.x - 1 - pos.x, pos.y, pos.z), Orientation::mirrored_x);
+    }
+
+    /// Return the schematic mirrored along the Z axis
+    pub fn mirrored_z(&self) -> Self {
+        let size = self.size;
+        return self.transformed(size, |pos| UVec3::new(pos.x, pos.y, size.z - 1 - pos.z), Orientation::mirrored_z);
+    }
+}
+
+impl<T: VoxelSet> Schematic<T>
+where
+    T::Id: Serialize + DeserializeOwned,
+{
+    /// Read a schematic from a RON file
+    pub fn load(path: impl AsRef<Path>) -> io::Result<Self> {
+        let content = fs::read_to_string(path)?;
+        let file: SchematicFile<T::Id> = ron::from_str(&content).map_err(|e| io::Error::new(io::ErrorKind::InvalidData, e))?;
+        let voxels = match file.voxels {
+            SchematicVoxels::Raw(voxels) => voxels,
+            SchematicVoxels::Palette { palette, indices } => indices
+                .iter()
+                .map(|index| palette.get(*index as usize).copied())
+                .collect::<Option<Vec<_>>>()
+                .ok_or_else(|| io::Error::new(io::ErrorKind::InvalidData, "palette index out of range"))?,
+        };
+        if voxel_count(file.size) != Some(voxels.len()) {
+            return Err(io::Error::new(io::ErrorKind::InvalidData, "voxel count doesn't match the schematic size"));
+        }
+        return Ok(Self { size: file.size, voxels });
+    }
+
+    /// Write the schematic to a RON file, with `palette` voxels are stored as indices in the list of distinct ids
+    pub fn save(&self, path: impl AsRef<Path>, palette: bool) -> io::Result<()> {
+        let voxels = if palette {
+            let mut palette = Vec::<T::Id>::new();
+            let mut indices = Vec::with_capacity(self.voxels.len());
+            for voxel_id in self.voxels.iter() {
+                let index = match palette.iter().position(|id| id == voxel_id) {
+                    Some(index) => index,
+                    None => {
+                        palette.push(*voxel_id);
+                        palette.len() - 1
+                    }
+                };
+                indices.push(index as u32);
+            }
+            SchematicVoxels::Palette { palette, indices }
+        } else {
+            SchematicVoxels::Raw(self.voxels.clone())
+        };
+        let file = SchematicFile { size: self.size, voxels };
+        let content = ron::to_string(&file).map_err(|e| io::Error::new(io::ErrorKind::InvalidData, e))?;
+        return fs::write(path, content);
+    }
+}
+
+impl<T: VoxelSet> VoxelWorld<T> {
+    /// Write `schematic` with its minimum corner at `offset`, returning the modified chunks
+    ///
+    /// With `skip_air` the default voxels of the schematic leave the world unchanged
+    pub fn paste_schematic(&mut self, schematic: &Schematic<T>, offset: IVec3, skip_air: bool) -> HashSet<IVec2> {
+        if schematic.voxels.is_empty() {
+            return HashSet::new();
+        }
+        let max = offset + schematic.size.as_ivec3() - IVec3::ONE;
+        return self.edit_voxels(offset, max, |pos, _| {
+            let voxel_id = schematic.get_voxel_id((pos - offset).as_uvec3());
+            return (!skip_air || voxel_id != T::get_default_voxel_id()).then_some(voxel_id);
+        });
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use std::{env, fs, io};
+
+    use bevy::math::{IVec2, IVec3, UVec3};
+
+    use crate::{
+        region::Region,
+        test_util::{empty_world, filled_chunk, TestSet},
+    };
+
+    use super::{Schematic, SchematicFile, SchematicVoxels};
+
+    /// 3x2x1 schematic with a stair facing North at x = 0
+    fn stairs() -> Schematic<TestSet> {
+        let mut schematic = Schematic::new(UVec3::new(3, 2, 1));
+        schematic.set_voxel_id(UVec3::new(0, 0, 0), 10);
+        schematic.set_voxel_id(UVec3::new(1, 0, 0), 1);
+        schematic.set_voxel_id(UVec3::new(2, 1, 0), 2);
+        return schematic;
+    }
+
+    #[test]
+    fn rotates_and_mirrors_voxels_and_orientations() {
+        let rotated = stairs().rotated_y(1);
+        assert_eq!(rotated.size(), UVec3::new(1, 2, 3));
+        // X+ goes to Z-, the stair now faces West
+        assert_eq!(rotated.get_voxel_id(UVec3::new(0, 0, 2)), 13);
+        assert_eq!(rotated.get_voxel_id(UVec3::new(0, 0, 1)), 1);
+        assert_eq!(rotated.get_voxel_id(UVec3::new(0, 1, 0)), 2);
+        assert_eq!(stairs().rotated_y(4).voxels, stairs().voxels);
+        assert_eq!(stairs().rotated_y(-1).voxels, stairs().rotated_y(3).voxels);
+
+        let mirrored = stairs().mirrored_x();
+        assert_eq!(mirrored.get_voxel_id(UVec3::new(2, 0, 0)), 11);
+        assert_eq!(mirrored.get_voxel_id(UVec3::new(0, 1, 0)), 2);
+        assert_eq!(stairs().mirrored_z().voxels, stairs().voxels);
+    }
+
+    #[test]
+    fn copies_and_pastes_through_file() {
+        let mut world = empty_world([IVec2::ZERO]);
+        world.insert_chunk(IVec2::X, filled_chunk(5));
+        world.paste_schematic(&stairs(), IVec3::new(2, 10, 2), false);
+
+        let copy = Schematic::from_world(&world, &Region::new_box(IVec3::new(2, 10, 2), IVec3::new(4, 11, 2)));
+        assert_eq!(copy.voxels, stairs().voxels);
+
+        for palette in [true, false] {
+            let path = env::temp_dir().join(format!("voxel_engine_schematic_{}.ron", palette));
+            copy.save(&path, palette).unwrap();
+            assert_eq!(Schematic::<TestSet>::load(&path).unwrap().voxels, copy.voxels);
+        }
+        // Sizes whose voxel count overflows are rejected instead of wrapping around
+        for size in [UVec3::new(65536, 65536, 1), UVec3::splat(u32::MAX)] {
+            let path = env::temp_dir().join("voxel_engine_schematic_overflow.ron");
+            let file = SchematicFile::<u8> { size, voxels: SchematicVoxels::Raw(vec![]) };
+            fs::write(&path, ron::to_string(&file).unwrap()).unwrap();
+            assert!(matches!(Schematic::<TestSet>::load(&path), Err(error) if error.kind() == io::ErrorKind::InvalidData));
+        }
+
+        // Across the chunk border, air of the schematic keeps the voxels of the world
+        let modified = world.paste_schematic(&copy, IVec3::new(14, 0, 0), true);
+        assert_eq!(modified, [IVec2::ZERO, IVec2::X].into_iter().collect());
+        assert_eq!(world.get_voxel_id(IVec3::new(16, 1, 0)), 2);
+        assert_eq!(world.get_voxel_id(IVec3::new(16, 0, 0)), 5);
+        world.paste_schematic(&copy, IVec3::new(14, 0, 0), false);
+        assert_eq!(world.get_voxel_id(IVec3::new(16, 0, 0)), 0);
+    }
+}
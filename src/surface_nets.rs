@@ -0,0 +1,165 @@
+use std::collections::HashMap;
+
+use bevy::{
+    math::{IVec2, IVec3, Vec3},
+    render::{
+        mesh::{Indices, Mesh, PrimitiveTopology},
+        render_asset::RenderAssetUsages,
+    },
+};
+
+use crate::{
+    chunk::{HEIGHT, WIDTH},
+    voxel::VoxelSet,
+    world::VoxelWorld,
+};
+
+/// Axes of the surface nets edges, each one followed by the two other axes in right-handed order
+const AXES: [(IVec3, IVec3, IVec3); 3] = [
+    (IVec3::X, IVec3::Y, IVec3::Z),
+    (IVec3::Y, IVec3::Z, IVec3::X),
+    (IVec3::Z, IVec3::X, IVec3::Y),
+];
+
+impl<T: VoxelSet> VoxelWorld<T> {
+    /// Create a smooth mesh of a chunk using surface nets, sampling cells of `2^lod` voxels
+    ///
+    /// The density is sampled at voxel centers, non transparent voxels being inside the surface.
+    /// Vertices only depend on the world content, so two neighbour chunks meshed at the same
+    /// level of detail share their border vertices and stitch without cracks.
+    pub fn create_chunk_smooth_mesh(&self, pos: IVec2, lod: u32) -> Mesh {
+        let size = 1 << lod;
+        let width = WIDTH as i32 / size;
+        let height = HEIGHT as i32 / size;
+        let chunk_origin = IVec3::new(pos.x * width, 0, pos.y * width);
+
+        let mut positions: Vec<[f32; 3]> = vec![];
+        let mut indices: Vec<u32> = vec![];
+        let mut cell_vertices: HashMap<IVec3, u32> = HashMap::new();
+
+        // Every edge between a voxel of the chunk and its positive neighbour is owned by this chunk,
+        // the bottom layer starts under the world to close the surface like the blocky mesher does
+        for x in 0..width {
+            for y in -1..height {
+                for z in 0..width {
+                    let voxel_pos = chunk_origin + IVec3::new(x, y, z);
+                    let inside = self.is_inside_surface(voxel_pos, lod);
+                    for (axis, u, v) in AXES {
+                        if inside == self.is_inside_surface(voxel_pos + axis, lod) {
+                            continue;
+                        }
+                        let mut quad = [voxel_pos - u - v, voxel_pos - v, voxel_pos, voxel_pos - u].map(|cell| {
+                            *cell_vertices.entry(cell).or_insert_with(|| {
+                                let vertex = (self.get_cell_vertex(cell, lod) * size as f32) - (chunk_origin * size).as_vec3();
+                                positions.push(vertex.to_array());
+                                (positions.len() - 1) as u32
+                            })
+                        });
+                        if !inside {
+                            quad.reverse();
+                        }
+                        indices.extend_from_slice(&[quad[0], quad[1], quad[2], quad[0], quad[2], quad[3]]);
+                    }
+                }
+            }
+        }
+
+        let mut normals = vec![Vec3::ZERO; positions.len()];
+        for triangle in indices.chunks(3) {
+            let [a, b, c] = [triangle[0], triangle[1], triangle[2]].map(|i| Vec3::from_array(positions[i as usize]));
+            let normal = (b - a).cross(c - a);
+            for i in triangle {
+                normals[*i as usize] += normal;
+            }
+        }
+        let normals: Vec<[f32; 3]> = normals.into_iter().map(|n| n.normalize_or_zero().to_array()).collect();
+
+        return Mesh::new(PrimitiveTopology::TriangleList, RenderAssetUsages::default())
+            .with_inserted_attribute(Mesh::ATTRIBUTE_POSITION, positions)
+            .with_inserted_attribute(Mesh::ATTRIBUTE_NORMAL, normals)
+            .with_inserted_indices(Indices::U32(indices));
+    }
+
+    fn is_inside_surface(&self, cell: IVec3, lod: u32) -> bool {
+        return !T::is_transparent(self.get_lod_voxel_id(cell, lod));
+    }
+
+    /// Return the vertex of the cell between the centers of voxels `cell` and `cell + 1`,
+    /// placed at the mean of the crossings of the surface with the cell edges (in cell units)
+    fn get_cell_vertex(&self, cell: IVec3, lod: u32) -> Vec3 {
+        let mut corners = [false; 8];
+        for (i, corner) in corners.iter_mut().enumerate() {
+            let offset = IVec3::new(i as i32 & 1, (i as i32 >> 1) & 1, (i as i32 >> 2) & 1);
+            *corner = self.is_inside_surface(cell + offset, lod);
+        }
+        let mut sum = Vec3::ZERO;
+        let mut count = 0;
+        for i in 0..8 {
+            for bit in [1, 2, 4] {
+                let j = i | bit;
+                if j != i && corners[i] != corners[j] {
+                    let a = Vec3::new((i & 1) as f32, ((i >> 1) & 1) as f32, ((i >> 2) & 1) as f32);
+                    let b = Vec3::new((j & 1) as f32, ((j >> 1) & 1) as f32, ((j >> 2) & 1) as f32);
+                    sum += (a + b) / 2.0;
+                    count += 1;
+                }
+            }
+        }
+        let center = if count == 0 { Vec3::splat(0.5) } else { sum / count as f32 };
+        return cell.as_vec3() + Vec3::splat(0.5) + center;
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use std::collections::HashMap;
+
+    use bevy::{
+        math::{IVec2, IVec3},
+        render::mesh::{Indices, Mesh, VertexAttributeValues},
+    };
+
+    use crate::{chunk::WIDTH, region::Region, test_util::empty_world};
+
+    type Vertex = [i32; 3];
+
+    /// Add the directed edges of the triangles of a chunk mesh, with vertices in world coordinates
+    fn add_edges(edges: &mut HashMap<(Vertex, Vertex), i32>, mesh: &Mesh, chunk_pos: IVec2) {
+        let (Some(VertexAttributeValues::Float32x3(positions)), Some(Indices::U32(indices))) =
+            (mesh.attribute(Mesh::ATTRIBUTE_POSITION), mesh.indices())
+        else {
+            panic!("mesh without positions or indices");
+        };
+        // Vertices are rounded so that both chunks give the same key for the same position
+        let vertex = |index: u32| -> Vertex {
+            let [x, y, z] = positions[index as usize];
+            let (x, z) = (x + (chunk_pos.x * WIDTH as i32) as f32, z + (chunk_pos.y * WIDTH as i32) as f32);
+            return [x, y, z].map(|coord| (coord * 1000.0).round() as i32);
+        };
+        for triangle in indices.chunks_exact(3) {
+            for i in 0..3 {
+                *edges.entry((vertex(triangle[i]), vertex(triangle[(i + 1) % 3]))).or_default() += 1;
+            }
+        }
+    }
+
+    /// A closed surface uses every edge as many times in each direction
+    fn is_closed(edges: &HashMap<(Vertex, Vertex), i32>) -> bool {
+        return edges.iter().all(|((a, b), count)| edges.get(&(*b, *a)) == Some(count));
+    }
+
+    #[test]
+    fn stitches_neighbour_chunks() {
+        let mut world = empty_world([IVec2::ZERO, IVec2::X]);
+        // A hill on the border between both chunks, cut by the bottom of the world
+        world.fill_region(&Region::Sphere { center: IVec3::new(WIDTH as i32, 2, 8), radius: 6.0 }, 1);
+        for lod in [0, 1] {
+            let mut edges = HashMap::new();
+            add_edges(&mut edges, &world.create_chunk_smooth_mesh(IVec2::ZERO, lod), IVec2::ZERO);
+            assert!(!is_closed(&edges));
+            // The border vertices of both chunks coincide, leaving no crack
+            add_edges(&mut edges, &world.create_chunk_smooth_mesh(IVec2::X, lod), IVec2::X);
+            assert!(is_closed(&edges));
+        }
+    }
+}
@@ -0,0 +1,64 @@
+use bevy::math::IVec2;
+
+use crate::{
+    chunk::{Chunk, HEIGHT, WIDTH},
+    voxel::{Orientation, Voxel, VoxelSet},
+    world::VoxelWorld,
+};
+
+/// 0 is air, 20 is transparent glass, every other id is a solid grass voxel and ids 10 to 13 are stairs facing North, South, East and West
+pub(crate) struct TestSet;
+
+impl VoxelSet for TestSet {
+    type Id = u8;
+
+    fn get_voxel_by_id(voxel_id: Self::Id) -> Voxel {
+        match voxel_id {
+            0 => Voxel::Air,
+            20 => Voxel::Glass,
+            _ => Voxel::Grass,
+        }
+    }
+
+    fn is_transparent(voxel_id: Self::Id) -> bool {
+        voxel_id == 0 || voxel_id == 20
+    }
+
+    fn get_default_voxel_id() -> Self::Id {
+        0
+    }
+
+    fn get_orientation(voxel_id: Self::Id) -> Option<Orientation> {
+        match voxel_id {
+            10 => Some(Orientation::North),
+            11 => Some(Orientation::South),
+            12 => Some(Orientation::East),
+            13 => Some(Orientation::West),
+            _ => None,
+        }
+    }
+
+    fn with_orientation(_voxel_id: Self::Id, orientation: Orientation) -> Self::Id {
+        match orientation {
+            Orientation::North => 10,
+            Orientation::South => 11,
+            Orientation::East => 12,
+            _ => 13,
+        }
+    }
+}
+
+/// Chunk filled with `voxel_id`
+pub(crate) fn filled_chunk(voxel_id: u8) -> Chunk<TestSet> {
+    return Chunk::new([[[voxel_id; WIDTH]; HEIGHT]; WIDTH]);
+}
+
+/// World with an empty chunk at each of `positions`, no chunk is left dirty
+pub(crate) fn empty_world(positions: impl IntoIterator<Item = IVec2>) -> VoxelWorld<TestSet> {
+    let mut world = VoxelWorld::new(Default::default());
+    for pos in positions {
+        world.insert_chunk(pos, filled_chunk(0));
+    }
+    world.take_dirty_chunks();
+    return world;
+}
@@ -0,0 +1,319 @@
+use std::{
+    fs::File,
+    io::{self, BufReader, BufWriter, Read, Write},
+    path::Path,
+};
+
+use bevy::math::UVec3;
+
+use crate::{
+    region::Region,
+    schematic::Schematic,
+    voxel::{ColorTable, VoxelSet},
+    world::VoxelWorld,
+};
+
+/// Version written in the header of exported files
+pub const VOX_VERSION: u32 = 150;
+
+/// Largest model size on each axis, positions are stored on a byte
+pub const VOX_MAX_SIZE: u32 = 256;
+
+/// Model of a `.vox` file, in MagicaVoxel coordinates (Z up)
+#[derive(Debug, Clone, PartialEq)]
+pub struct VoxModel {
+    pub size: UVec3,
+    /// Position and color index, from 1 to 255, of every voxel
+    pub voxels: Vec<(UVec3, u8)>,
+}
+
+/// Content of a MagicaVoxel `.vox` file, only the models and the palette are kept
+#[derive(Debug, Clone, PartialEq)]
+pub struct VoxFile {
+    pub models: Vec<VoxModel>,
+    /// RGBA color of each color index, index 0 is unused
+    pub palette: Vec<[u8; 4]>,
+}
+
+fn invalid_data(message: &str) -> io::Error {
+    return io::Error::new(io::ErrorKind::InvalidData, message.to_string());
+}
+
+fn read_u32(bytes: &[u8], offset: usize) -> io::Result<u32> {
+    let word = bytes.get(offset..offset + 4).ok_or_else(|| invalid_data("unexpected end of chunk"))?;
+    return Ok(u32::from_le_bytes([word[0], word[1], word[2], word[3]]));
+}
+
+/// Palette of files without RGBA chunk, a grey ramp
+fn default_palette() -> Vec<[u8; 4]> {
+    return (0..=255u8).map(|i| [i, i, i, 255]).collect();
+}
+
+impl VoxFile {
+    pub fn new() -> Self {
+        Self {
+            models: vec![],
+            palette: default_palette(),
+        }
+    }
+
+    pub fn read(mut reader: impl Read) -> io::Result<Self> {
+        let mut bytes = vec![];
+        reader.read_to_end(&mut bytes)?;
+        if bytes.get(0..4) != Some(b"VOX ".as_slice()) {
+            return Err(invalid_data("missing VOX header"));
+        }
+        let mut file = Self::new();
+        let mut size = None;
+        // Chunks are read one after the other, the children of MAIN come right after its empty content
+        let mut offset = 8;
+        while offset < bytes.len() {
+            let id = bytes.get(offset..offset + 4).ok_or_else(|| invalid_data("unexpected end of file"))?;
+            let content_size = read_u32(&bytes, offset + 4)? as usize;
+            let content_start = offset + 12;
+            let content = bytes
+                .get(content_start..content_start + content_size)
+                .ok_or_else(|| invalid_data("chunk content out of the file"))?;
+            match id {
+                b"MAIN" => {
+                    offset = content_start + content_size;
+                    continue;
+                }
+                b"SIZE" => {
+                    let model_size = UVec3::new(read_u32(content, 0)?, read_u32(content, 4)?, read_u32(content, 8)?);
+                    if model_size.cmpgt(UVec3::splat(VOX_MAX_SIZE)).any() {
+                        return Err(invalid_data(&format!("a model can't be larger than {} voxels", VOX_MAX_SIZE)));
+                    }
+                    size = Some(model_size);
+                }
+                b"XYZI" => {
+                    let size = size.take().ok_or_else(|| invalid_data("XYZI chunk without SIZE chunk"))?;
+                    let count = read_u32(content, 0)? as usize;
+                    let data = content.get(4..4 + count * 4).ok_or_else(|| invalid_data("XYZI chunk too short"))?;
+                    let voxels = data
+                        .chunks_exact(4)
+                        .map(|voxel| (UVec3::new(voxel[0] as u32, voxel[1] as u32, voxel[2] as u32), voxel[3]))
+                        .collect();
+                    file.models.push(VoxModel { size, voxels });
+                }
+                b"RGBA" => {
+                    // Entry i is the color of index i + 1
+                    let colors = content.get(0..1024).ok_or_else(|| invalid_data("RGBA chunk too short"))?;
+                    file.palette = [[0; 4]]
+                        .into_iter()
+                        .chain(colors.chunks_exact(4).take(255).map(|color| [color[0], color[1], color[2], color[3]]))
+                        .collect();
+                }
+                // Scene graph, materials, layers, ...
+                _ => {}
+            }
+            let children_size = read_u32(&bytes, offset + 8)? as usize;
+            offset = content_start + content_size + children_size;
+        }
+        return Ok(file);
+    }
+
+    pub fn write(&self, mut writer: impl Write) -> io::Result<()> {
+        let mut children = vec![];
+        let mut write_chunk = |id: &[u8; 4], content: &[u8]| {
+            children.extend_from_slice(id);
+            children.extend_from_slice(&(content.len() as u32).to_le_bytes());
+            children.extend_from_slice(&0u32.to_le_bytes());
+            children.extend_from_slice(content);
+        };
+        for model in self.models.iter() {
+            let size = [model.size.x, model.size.y, model.size.z];
+            write_chunk(b"SIZE", &size.iter().flat_map(|size| size.to_le_bytes()).collect::<Vec<_>>());
+            let mut content = (model.voxels.len() as u32).to_le_bytes().to_vec();
+            for (pos, color_index) in model.voxels.iter() {
+                content.extend_from_slice(&[pos.x as u8, pos.y as u8, pos.z as u8, *color_index]);
+            }
+            write_chunk(b"XYZI", &content);
+        }
+        let mut colors = [[0u8; 4]; 256];
+        for (index, color) in self.palette.iter().enumerate().skip(1).take(255) {
+            colors[index - 1] = *color;
+        }
+        write_chunk(b"RGBA", &colors.concat());
+
+        writer.write_all(b"VOX ")?;
+        writer.write_all(&VOX_VERSION.to_le_bytes())?;
+        writer.write_all(b"MAIN")?;
+        writer.write_all(&0u32.to_le_bytes())?;
+        writer.write_all(&(children.len() as u32).to_le_bytes())?;
+        writer.write_all(&children)?;
+        return Ok(());
+    }
+
+    pub fn load(path: impl AsRef<Path>) -> io::Result<Self> {
+        return Self::read(BufReader::new(File::open(path)?));
+    }
+
+    pub fn save(&self, path: impl AsRef<Path>) -> io::Result<()> {
+        let mut writer = BufWriter::new(File::create(path)?);
+        self.write(&mut writer)?;
+        return writer.flush();
+    }
+
+    /// Convert a model into a schematic, MagicaVoxel Z becomes Y and Y becomes -Z
+    ///
+    /// Colors are turned into ids with `colors`, return `None` if there is no model at `index`
+    pub fn to_schematic<T: VoxelSet>(&self, index: usize, colors: &ColorTable<T>) -> Option<Schematic<T>> {
+        let model = self.models.get(index)?;
+        let mut schematic = Schematic::new(UVec3::new(model.size.x, model.size.z, model.size.y));
+        for (pos, color_index) in model.voxels.iter() {
+            if pos.cmpge(model.size).any() {
+                continue;
+            }
+            let color = self.palette.get(*color_index as usize).copied().unwrap_or([0; 4]);
+            if let Some(voxel_id) = colors.get_voxel_id(color) {
+                schematic.set_voxel_id(UVec3::new(pos.x, pos.z, model.size.y - 1 - pos.y), voxel_id);
+            }
+        }
+        return Some(schematic);
+    }
+
+    /// Create a file with a model for each schematic, the palette holds the colors of `colors`
+    ///
+    /// Default voxels and voxels whose id has no color are left empty, `colors` can't hold more than 255 colors
+    pub fn from_schematics<T: VoxelSet>(schematics: &[Schematic<T>], colors: &ColorTable<T>) -> io::Result<Self> {
+        if colors.colors().len() > 255 {
+            return Err(io::Error::new(io::ErrorKind::InvalidInput, "a palette can't hold more than 255 colors"));
+        }
+        let mut file = Self::new();
+        file.palette = [[0; 4]].into_iter().chain(colors.colors().iter().map(|(color, _)| *color)).collect();
+        for schematic in schematics.iter() {
+            let size = schematic.size();
+            if size.cmpgt(UVec3::splat(VOX_MAX_SIZE)).any() {
+                return Err(io::Error::new(io::ErrorKind::InvalidInput, format!("a model can't be larger than {} voxels", VOX_MAX_SIZE)));
+            }
+            let mut model = VoxModel {
+                size: UVec3::new(size.x, size.z, size.y),
+                voxels: vec![],
+            };
+            for pos in schematic.positions() {
+                let voxel_id = schematic.get_voxel_id(pos);
+                if voxel_id == T::get_default_voxel_id() {
+                    continue;
+                }
+                if let Some(index) = colors.colors().iter().position(|(_, id)| *id == voxel_id) {
+                    model.voxels.push((UVec3::new(pos.x, size.z - 1 - pos.z, pos.y), index as u8 + 1));
+                }
+            }
+            file.models.push(model);
+        }
+        return Ok(file);
+    }
+
+    /// Create a file with a single model holding the voxels of `region`
+    pub fn from_world<T: VoxelSet>(world: &VoxelWorld<T>, region: &Region, colors: &ColorTable<T>) -> io::Result<Self> {
+        return Self::from_schematics(&[Schematic::from_world(world, region)], colors);
+    }
+}
+
+impl Default for VoxFile {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use std::io;
+
+    use bevy::math::{IVec2, IVec3, UVec3};
+
+    use crate::{
+        region::Region,
+        test_util::{empty_world, TestSet},
+        voxel::ColorTable,
+    };
+
+    use super::{VoxFile, VoxModel};
+
+    fn chunk(id: &[u8; 4], content: &[u8], children: &[u8]) -> Vec<u8> {
+        let mut bytes = id.to_vec();
+        bytes.extend_from_slice(&(content.len() as u32).to_le_bytes());
+        bytes.extend_from_slice(&(children.len() as u32).to_le_bytes());
+        bytes.extend_from_slice(content);
+        bytes.extend_from_slice(children);
+        return bytes;
+    }
+
+    fn words(words: &[u32]) -> Vec<u8> {
+        return words.iter().flat_map(|word| word.to_le_bytes()).collect();
+    }
+
+    /// Two models, a scene graph node to skip and a palette where index 1 is red and index 2 green
+    fn hand_built_file() -> Vec<u8> {
+        let mut colors = vec![0u8; 1024];
+        colors[0..8].copy_from_slice(&[255, 0, 0, 255, 0, 255, 0, 255]);
+        let children = [
+            chunk(b"PACK", &words(&[2]), &[]),
+            chunk(b"SIZE", &words(&[2, 3, 4]), &[]),
+            chunk(b"XYZI", &[words(&[2]), vec![0, 0, 0, 1, 1, 2, 3, 2]].concat(), &[]),
+            chunk(b"SIZE", &words(&[1, 1, 1]), &[]),
+            chunk(b"XYZI", &[words(&[1]), vec![0, 0, 0, 2]].concat(), &[]),
+            chunk(b"nTRN", &words(&[0, 0]), &[]),
+            chunk(b"RGBA", &colors, &[]),
+        ]
+        .concat();
+        return [b"VOX ".to_vec(), words(&[150]), chunk(b"MAIN", &[], &children)].concat();
+    }
+
+    fn colors() -> ColorTable<TestSet> {
+        return ColorTable::new().with_color([255, 0, 0, 255], 1).with_color([0, 255, 0, 255], 2);
+    }
+
+    #[test]
+    fn reads_hand_built_file() {
+        let file = VoxFile::read(hand_built_file().as_slice()).unwrap();
+        assert_eq!(file.models.len(), 2);
+        assert_eq!(file.models[0], VoxModel {
+            size: UVec3::new(2, 3, 4),
+            voxels: vec![(UVec3::new(0, 0, 0), 1), (UVec3::new(1, 2, 3), 2)],
+        });
+        assert_eq!(file.palette[1], [255, 0, 0, 255]);
+        assert_eq!(file.palette[2], [0, 255, 0, 255]);
+
+        // Z up becomes Y up, Y becomes -Z
+        let schematic = file.to_schematic(0, &colors()).unwrap();
+        assert_eq!(schematic.size(), UVec3::new(2, 4, 3));
+        assert_eq!(schematic.get_voxel_id(UVec3::new(0, 0, 2)), 1);
+        assert_eq!(schematic.get_voxel_id(UVec3::new(1, 3, 0)), 2);
+        assert_eq!(schematic.get_voxel_id(UVec3::new(0, 0, 0)), 0);
+        assert!(file.to_schematic(2, &colors()).is_none());
+
+        assert!(VoxFile::read(b"RIFF".as_slice()).is_err());
+        assert!(VoxFile::read(&hand_built_file()[..40]).is_err());
+        // Sizes past VOX_MAX_SIZE would overflow the schematic
+        let mut huge = hand_built_file();
+        let size = huge.windows(4).position(|id| id == b"SIZE").unwrap() + 12;
+        huge[size..size + 12].copy_from_slice(&words(&[2, 65536, 65536]));
+        assert_eq!(VoxFile::read(huge.as_slice()).unwrap_err().kind(), io::ErrorKind::InvalidData);
+    }
+
+    #[test]
+    fn round_trips_files_and_world_regions() {
+        let file = VoxFile::read(hand_built_file().as_slice()).unwrap();
+        let mut bytes = vec![];
+        file.write(&mut bytes).unwrap();
+        assert_eq!(VoxFile::read(bytes.as_slice()).unwrap(), file);
+
+        let mut world = empty_world([IVec2::ZERO]);
+        world.set_voxel_id(IVec3::new(1, 1, 1), 1);
+        world.set_voxel_id(IVec3::new(2, 3, 1), 2);
+        world.set_voxel_id(IVec3::new(3, 1, 4), 1);
+        let region = Region::new_box(IVec3::new(1, 1, 1), IVec3::new(3, 3, 4));
+        let exported = VoxFile::from_world(&world, &region, &colors()).unwrap();
+        assert_eq!(exported.models[0].voxels.len(), 3);
+
+        let mut bytes = vec![];
+        exported.write(&mut bytes).unwrap();
+        let imported = VoxFile::read(bytes.as_slice()).unwrap().to_schematic(0, &colors()).unwrap();
+        world.paste_schematic(&imported, IVec3::new(8, 1, 8), false);
+        for pos in [IVec3::new(0, 0, 0), IVec3::new(1, 2, 0), IVec3::new(2, 0, 3), IVec3::new(1, 1, 1)] {
+            assert_eq!(world.get_voxel_id(IVec3::new(8, 1, 8) + pos), world.get_voxel_id(IVec3::new(1, 1, 1) + pos));
+        }
+    }
+}
@@ -1,7 +1,8 @@
 use std::fmt::Debug;
 
-use bevy::{log::info, render::{mesh::{Indices, Mesh, PrimitiveTopology}, render_asset::RenderAssetUsages}};
+use bevy::{log::info, math::Vec3, render::{mesh::{Indices, Mesh, PrimitiveTopology}, render_asset::RenderAssetUsages}};
 
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
 pub enum Orientation {
     /// X+
     North,
@@ -17,9 +18,54 @@ pub enum Orientation {
     Down,
 }
 
+impl Orientation {
+    /// Return the horizontal orientation closest to `direction`, ignoring its height
+    pub fn from_horizontal_direction(direction: Vec3) -> Self {
+        if direction.x.abs() >= direction.z.abs() {
+            return if direction.x >= 0.0 { Orientation::North } else { Orientation::South };
+        }
+        return if direction.z >= 0.0 { Orientation::East } else { Orientation::West };
+    }
+
+    /// Return the orientation after `quarter_turns` rotations of 90° around Y, counterclockwise seen from above (X+ goes to Z-)
+    pub fn rotated_y(self, quarter_turns: i32) -> Self {
+        let mut orientation = self;
+        for _ in 0..quarter_turns.rem_euclid(4) {
+            orientation = match orientation {
+                Orientation::North => Orientation::West,
+                Orientation::West => Orientation::South,
+                Orientation::South => Orientation::East,
+                Orientation::East => Orientation::North,
+                vertical => vertical,
+            };
+        }
+        return orientation;
+    }
+
+    /// Return the orientation mirrored along the X axis, North and South are swapped
+    pub fn mirrored_x(self) -> Self {
+        match self {
+            Orientation::North => return Orientation::South,
+            Orientation::South => return Orientation::North,
+            other => return other,
+        }
+    }
+
+    /// Return the orientation mirrored along the Z axis, East and West are swapped
+    pub fn mirrored_z(self) -> Self {
+        match self {
+            Orientation::East => return Orientation::West,
+            Orientation::West => return Orientation::East,
+            other => return other,
+        }
+    }
+}
+
 pub enum Voxel {
     Air,
     Grass,
+    Glass,
+    Water,
     Error,
 }
 
@@ -65,14 +111,93 @@ impl Voxel {
     }
 }
 
-pub trait VoxelSet {
+pub trait VoxelSet: Send + Sync + 'static {
 
-    type Id: Copy + Clone + PartialEq + Debug;
+    type Id: Copy + Clone + PartialEq + Debug + Send + Sync;
 
     fn get_voxel_by_id(voxel_id: Self::Id) -> Voxel;
 
+    /// Transparent voxels don't hide the faces of their neighbours, the ones with faces
+    /// (glass, water, ...) are meshed in a separate translucent pass
     fn is_transparent(voxel_id: Self::Id) -> bool;
 
     /// Used in the generation of a chunk mesh
     fn get_default_voxel_id() -> Self::Id;
-}
\ No newline at end of file
+
+    /// Direction an oriented voxel (stairs, logs, ...) is facing, `None` for voxels that look the same from every side
+    fn get_orientation(_voxel_id: Self::Id) -> Option<Orientation> {
+        None
+    }
+
+    /// Return the id of the same voxel facing `orientation`, used to rotate and mirror schematics
+    fn with_orientation(voxel_id: Self::Id, _orientation: Orientation) -> Self::Id {
+        voxel_id
+    }
+}
+
+/// Voxels of the demo and of the `export` tool : air, grass, glass and water
+pub struct BasicSet;
+
+impl VoxelSet for BasicSet {
+    type Id = u8;
+
+    fn get_voxel_by_id(voxel_id: Self::Id) -> Voxel {
+        match voxel_id {
+            0 => Voxel::Air,
+            1 => Voxel::Grass,
+            2 => Voxel::Glass,
+            3 => Voxel::Water,
+            _ => Voxel::Error,
+        }
+    }
+
+    fn is_transparent(voxel_id: Self::Id) -> bool {
+        match voxel_id {
+            0 | 2 | 3 => true,
+            _ => false,
+        }
+    }
+
+    fn get_default_voxel_id() -> Self::Id {
+        0
+    }
+}
+
+/// Colors of the voxel ids of `T`, used to import and export `.vox` files and to pick voxels from heightmap color maps
+///
+/// A color missing from the table becomes the id of the closest color
+pub struct ColorTable<T: VoxelSet> {
+    colors: Vec<([u8; 4], T::Id)>,
+}
+
+impl<T: VoxelSet> ColorTable<T> {
+    pub fn new() -> Self {
+        Self { colors: vec![] }
+    }
+
+    /// Map `color` to `voxel_id`, the first color given for an id is used on export
+    pub fn with_color(mut self, color: [u8; 4], voxel_id: T::Id) -> Self {
+        self.colors.push((color, voxel_id));
+        return self;
+    }
+
+    /// Colors and their ids, in insertion order
+    pub fn colors(&self) -> &[([u8; 4], T::Id)] {
+        return &self.colors;
+    }
+
+    /// Return the id of the closest color, `None` for transparent colors or an empty table
+    pub fn get_voxel_id(&self, color: [u8; 4]) -> Option<T::Id> {
+        if color[3] == 0 {
+            return None;
+        }
+        let distance = |other: [u8; 4]| -> u32 { (0..3).map(|i| (color[i] as i32 - other[i] as i32).pow(2) as u32).sum() };
+        return self.colors.iter().min_by_key(|(other, _)| distance(*other)).map(|(_, voxel_id)| *voxel_id);
+    }
+}
+
+impl<T: VoxelSet> Default for ColorTable<T> {
+    fn default() -> Self {
+        Self::new()
+    }
+}
@@ -0,0 +1,265 @@
+use std::marker::PhantomData;
+
+use bevy::{
+    app::{App, Plugin, Update},
+    ecs::{
+        component::Component,
+        entity::Entity,
+        query::Without,
+        schedule::{common_conditions::in_state, IntoSystemConfigs},
+        system::{Commands, Query, Res},
+    },
+    math::Vec3,
+    time::Time,
+    transform::components::Transform,
+};
+
+use crate::{
+    actions::{Action, ActionState},
+    camera::{CameraId, CameraMode, ControlledCamera, PauseState},
+    collision::{Aabb, CollisionResult, COLLISION_EPSILON},
+    voxel::VoxelSet,
+    world::VoxelWorld,
+};
+
+/// State and settings of a walking camera, speeds are in voxels per second
+#[derive(Debug, Clone, Component)]
+pub struct WalkController {
+    pub velocity: Vec3,
+    pub grounded: bool,
+    pub crouching: bool,
+    /// Width and height of the player box
+    pub width: f32,
+    pub height: f32,
+    pub crouch_height: f32,
+    /// Distance between the eyes (camera) and the top of the box
+    pub eye_offset: f32,
+    /// Highest ledge climbed without jumping
+    pub step_height: f32,
+    pub walk_speed: f32,
+    pub sprint_speed: f32,
+    pub crouch_speed: f32,
+    pub jump_speed: f32,
+    pub gravity: f32,
+}
+
+impl Default for WalkController {
+    fn default() -> Self {
+        Self {
+            velocity: Vec3::ZERO,
+            grounded: false,
+            crouching: false,
+            width: 0.6,
+            height: 1.8,
+            crouch_height: 1.5,
+            eye_offset: 0.2,
+            step_height: 1.0,
+            walk_speed: 4.3,
+            sprint_speed: 5.6,
+            crouch_speed: 1.3,
+            jump_speed: 8.0,
+            gravity: 28.0,
+        }
+    }
+}
+
+impl WalkController {
+    fn current_height(&self) -> f32 {
+        return if self.crouching { self.crouch_height } else { self.height };
+    }
+
+    /// Return the player box for a camera at `eye`
+    pub fn aabb(&self, eye: Vec3) -> Aabb {
+        let height = self.current_height();
+        let feet = eye - Vec3::new(0.0, height - self.eye_offset, 0.0);
+        return Aabb::from_feet(feet, Vec3::new(self.width, height, self.width));
+    }
+
+    /// Return the camera position for a player box
+    pub fn eye(&self, aabb: Aabb) -> Vec3 {
+        return Vec3::new((aabb.min.x + aabb.max.x) / 2.0, aabb.max.y - self.eye_offset, (aabb.min.z + aabb.max.z) / 2.0);
+    }
+}
+
+/// Add walking to the camera `id` spawned by `CameraPlugin`, colliding with the `VoxelWorld<T>` resource
+///
+/// Moved by the actions of `ActionPlugin`, `Action::ToggleMode` switches between flying and walking
+pub struct WalkPlugin<T: VoxelSet> {
+    id: u32,
+    controller: WalkController,
+    _voxel_set: PhantomData<T>,
+}
+
+impl<T: VoxelSet> Plugin for WalkPlugin<T> {
+    fn build(&self, app: &mut App) {
+        let id = self.id;
+        let controller = self.controller.clone();
+        app.add_systems(
+            Update,
+            move |mut commands: Commands, cameras: Query<(Entity, &CameraId), Without<WalkController>>| {
+                for (entity, camera_id) in cameras.iter() {
+                    if camera_id.0 == id {
+                        commands.entity(entity).insert(controller.clone());
+                    }
+                }
+            },
+        );
+        // Shared by every camera, only added by the first plugin
+        if app.is_plugin_added::<Self>() {
+            return;
+        }
+        app.add_systems(Update, (toggle_camera_mode, walk::<T>).run_if(in_state(PauseState::Running)));
+    }
+
+    /// One plugin is added per walking camera
+    fn is_unique(&self) -> bool {
+        false
+    }
+}
+
+impl<T: VoxelSet> WalkPlugin<T> {
+    pub fn new(id: u32) -> Self {
+        Self {
+            id: id,
+            controller: WalkController::default(),
+            _voxel_set: PhantomData,
+        }
+    }
+
+    pub fn with_walk_speed(mut self, walk_speed: f32) -> Self {
+        self.controller.walk_speed = walk_speed;
+        return self;
+    }
+    pub fn with_sprint_speed(mut self, sprint_speed: f32) -> Self {
+        self.controller.sprint_speed = sprint_speed;
+        return self;
+    }
+    pub fn with_jump_speed(mut self, jump_speed: f32) -> Self {
+        self.controller.jump_speed = jump_speed;
+        return self;
+    }
+    pub fn with_gravity(mut self, gravity: f32) -> Self {
+        self.controller.gravity = gravity;
+        return self;
+    }
+}
+
+fn toggle_camera_mode(
+    actions: Res<ActionState>,
+    controlled_camera: Res<ControlledCamera>,
+    mut cameras: Query<(&CameraId, &mut CameraMode, &mut WalkController)>,
+) {
+    for (camera_id, mut camera_mode, mut controller) in cameras.iter_mut() {
+        if camera_id.0 == controlled_camera.0 && actions.just_pressed(Action::ToggleMode) {
+            *camera_mode = match *camera_mode {
+                CameraMode::Fly => CameraMode::Walk,
+                CameraMode::Walk => CameraMode::Fly,
+                // Spectator modes are left with `Action::CycleCameraMode`
+                mode => mode,
+            };
+            controller.velocity = Vec3::ZERO;
+            controller.grounded = false;
+        }
+    }
+}
+
+fn walk<T: VoxelSet>(
+    time: Res<Time>,
+    actions: Res<ActionState>,
+    controlled_camera: Res<ControlledCamera>,
+    world: Res<VoxelWorld<T>>,
+    mut cameras: Query<(&CameraId, &mut Transform, &CameraMode, &mut WalkController)>,
+) {
+    let delta = time.delta_seconds();
+    for (camera_id, mut camera_transform, camera_mode, mut controller) in cameras.iter_mut() {
+        if *camera_mode != CameraMode::Walk {
+            continue;
+        }
+        // Cameras not controlled still fall
+        let pressed = |action: Action| camera_id.0 == controlled_camera.0 && actions.pressed(action);
+        let mut aabb = controller.aabb(camera_transform.translation);
+
+        // Standing up again needs room above the head
+        let crouching = pressed(Action::Crouch);
+        if crouching != controller.crouching {
+            let height_change = controller.height - controller.crouch_height;
+            let resized = if crouching {
+                Aabb::new(aabb.min, aabb.max - Vec3::new(0.0, height_change, 0.0))
+            } else {
+                Aabb::new(aabb.min, aabb.max + Vec3::new(0.0, height_change, 0.0))
+            };
+            if crouching || !world.is_aabb_colliding(resized) {
+                controller.crouching = crouching;
+                aabb = resized;
+            }
+        }
+
+        let mut forward = *camera_transform.forward();
+        forward.y = 0.0;
+        let forward = forward.normalize_or_zero();
+        let right = Vec3::new(-forward.z, 0.0, forward.x);
+        let mut direction = Vec3::ZERO;
+        if pressed(Action::MoveForward) {
+            direction += forward;
+        }
+        if pressed(Action::MoveBackward) {
+            direction -= forward;
+        }
+        if pressed(Action::MoveRight) {
+            direction += right;
+        }
+        if pressed(Action::MoveLeft) {
+            direction -= right;
+        }
+        let speed = if controller.crouching {
+            controller.crouch_speed
+        } else if pressed(Action::Sprint) {
+            controller.sprint_speed
+        } else {
+            controller.walk_speed
+        };
+        let horizontal_velocity = direction.normalize_or_zero() * speed;
+        controller.velocity.x = horizontal_velocity.x;
+        controller.velocity.z = horizontal_velocity.z;
+
+        if controller.grounded && pressed(Action::Jump) {
+            controller.velocity.y = controller.jump_speed;
+        }
+        controller.velocity.y -= controller.gravity * delta;
+
+        let can_step_up = controller.grounded && controller.velocity.y <= 0.0;
+        let result = move_with_step_up(world.as_ref(), aabb, controller.velocity * delta, if can_step_up { controller.step_height } else { 0.0 });
+        if result.grounded || result.hit_ceiling {
+            controller.velocity.y = 0.0;
+        }
+        controller.grounded = result.grounded;
+        camera_transform.translation = controller.eye(result.aabb);
+    }
+}
+
+/// Move the box like `VoxelWorld::move_aabb`, climbing obstacles up to `step_height` high when blocked by a wall
+fn move_with_step_up<T: VoxelSet>(world: &VoxelWorld<T>, aabb: Aabb, motion: Vec3, step_height: f32) -> CollisionResult {
+    let result = world.move_aabb(aabb, motion);
+    if !result.hit_wall || step_height <= 0.0 {
+        return result;
+    }
+
+    let raised = world.move_aabb(aabb, Vec3::new(0.0, step_height, 0.0));
+    if raised.hit_ceiling {
+        return result;
+    }
+    let moved = world.move_aabb(raised.aabb, Vec3::new(motion.x, 0.0, motion.z));
+    let lowered = world.move_aabb(moved.aabb, Vec3::new(0.0, motion.y.min(0.0) - step_height, 0.0));
+
+    let horizontal_distance = |motion: Vec3| Vec3::new(motion.x, 0.0, motion.z).length();
+    if horizontal_distance(moved.motion) <= horizontal_distance(result.motion) + COLLISION_EPSILON {
+        return result;
+    }
+    return CollisionResult {
+        aabb: lowered.aabb,
+        motion: lowered.aabb.min - aabb.min,
+        grounded: lowered.grounded,
+        hit_ceiling: false,
+        hit_wall: moved.hit_wall,
+    };
+}
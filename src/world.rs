@@ -1,24 +1,67 @@
-use bevy::{math::{IVec2, IVec3, UVec3, Vec3}, render::{mesh::{Indices, Mesh, PrimitiveTopology}, render_asset::RenderAssetUsages}};
+use std::{
+    collections::HashSet,
+    fs::File,
+    io::{self, BufReader, BufWriter, Read, Write},
+    path::Path,
+};
 
-use crate::{chunk::{self, Chunk, HEIGHT, WIDTH}, voxel::{Orientation, Voxel, VoxelSet}};
+use bevy::{ecs::system::Resource, math::{IVec2, IVec3, UVec3, Vec3}, render::{mesh::{Indices, Mesh, PrimitiveTopology}, render_asset::RenderAssetUsages}};
 
+use crate::{chunk::{self, Chunk, HEIGHT, WIDTH}, history::{EditHistory, EditRun}, voxel::{Orientation, Voxel, VoxelSet}};
+
+/// Algorithm used to turn the voxels of a chunk into a mesh
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum MeshingMode {
+    /// One cube per voxel, hidden faces are culled
+    Blocky,
+    /// Smooth isosurface built with surface nets, transparent voxels are considered empty
+    SurfaceNets,
+}
+
+#[derive(Resource)]
 pub struct VoxelWorld<T: VoxelSet> {
     /// Sorted in trigonometric order
-    pub quadrants: [Vec<Vec<Chunk<T>>>; 4],
+    pub quadrants: [Vec<Vec<Option<Chunk<T>>>>; 4],
+    pub meshing_mode: MeshingMode,
+    /// Chunks edited since their mesh was last generated
+    dirty_chunks: HashSet<IVec2>,
+    /// Changes not yet sent as Bevy events, see `events::send_world_events`
+    loaded_chunks: Vec<IVec2>,
+    unloaded_chunks: Vec<IVec2>,
+    voxel_changes: Vec<(IVec3, T::Id, T::Id)>,
+    /// Transactions that can be undone and redone
+    history: EditHistory<T::Id>,
 }
 
 impl<T: VoxelSet> VoxelWorld<T> {
-    pub fn new(quadrants: [Vec<Vec<Chunk<T>>>; 4]) -> Self {
+    pub fn new(quadrants: [Vec<Vec<Option<Chunk<T>>>>; 4]) -> Self {
         Self {
             quadrants: quadrants,
+            meshing_mode: MeshingMode::Blocky,
+            dirty_chunks: HashSet::new(),
+            loaded_chunks: vec![],
+            unloaded_chunks: vec![],
+            voxel_changes: vec![],
+            history: EditHistory::default(),
         }
     }
 
+    pub fn with_meshing_mode(mut self, meshing_mode: MeshingMode) -> Self {
+        self.meshing_mode = meshing_mode;
+        return self;
+    }
+
+    /// Limit the memory used by the undo history, in bytes
+    pub fn with_history_memory(mut self, max_memory: usize) -> Self {
+        self.history = EditHistory::new(max_memory);
+        return self;
+    }
+
     fn chunk_pos_signature(pos: IVec2) -> (bool, bool) {
         return (pos.x >= 0, pos.y >= 0);
     }
 
-    fn get_quadrant(&self, pos: IVec2) -> &Vec<Vec<Chunk<T>>> {
+    fn get_quadrant(&self, pos: IVec2) -> &Vec<Vec<Option<Chunk<T>>>> {
         let (x, y) = Self::chunk_pos_signature(pos);
         if x {
             if y {
@@ -35,18 +78,104 @@ impl<T: VoxelSet> VoxelWorld<T> {
         }
     }
 
+    fn get_quadrant_mut(&mut self, pos: IVec2) -> &mut Vec<Vec<Option<Chunk<T>>>> {
+        let (x, y) = Self::chunk_pos_signature(pos);
+        if x {
+            if y {
+                return &mut self.quadrants[0];
+            } else {
+                return &mut self.quadrants[3];
+            }
+        } else {
+            if y {
+                return &mut self.quadrants[1];
+            } else {
+                return &mut self.quadrants[2];
+            }
+        }
+    }
+
+    pub fn get_chunk_mut(&mut self, pos: IVec2) -> Option<&mut Chunk<T>> {
+        let quadrant = self.get_quadrant_mut(pos);
+
+        if quadrant.len() > pos.x.abs() as usize {
+            if quadrant[pos.x.abs() as usize].len() > pos.y.abs() as usize {
+                return quadrant[pos.x.abs() as usize][pos.y.abs() as usize].as_mut();
+            }
+        }
+
+        return None;
+    }
+
     pub fn get_chunk(&self, pos: IVec2) -> Option<&Chunk<T>> {
         let quadrant = self.get_quadrant(pos);
 
         if quadrant.len() > pos.x.abs() as usize {
             if quadrant[pos.x.abs() as usize].len() > pos.y.abs() as usize {
-                return Some(&quadrant[pos.x.abs() as usize][pos.y.abs() as usize]);
+                return quadrant[pos.x.abs() as usize][pos.y.abs() as usize].as_ref();
             }
         }
 
         return None;
     }
 
+    /// Load a chunk at `pos`, returning the chunk it replaces
+    ///
+    /// The chunk and its loaded neighbours are marked dirty since their border faces depend on it.
+    /// A chunk removed and inserted again before the events are sent is only reported as loaded.
+    /// Replacing a loaded chunk can't be undone, so it clears the undo history.
+    pub fn insert_chunk(&mut self, pos: IVec2, chunk: Chunk<T>) -> Option<Chunk<T>> {
+        let quadrant = self.get_quadrant_mut(pos);
+        let (x, y) = (pos.x.abs() as usize, pos.y.abs() as usize);
+        if quadrant.len() <= x {
+            quadrant.resize_with(x + 1, Vec::new);
+        }
+        if quadrant[x].len() <= y {
+            quadrant[x].resize_with(y + 1, || None);
+        }
+        let previous_chunk = quadrant[x][y].replace(chunk);
+        self.unloaded_chunks.retain(|unloaded_pos| *unloaded_pos != pos);
+        if !self.loaded_chunks.contains(&pos) {
+            self.loaded_chunks.push(pos);
+        }
+        self.mark_chunk_and_neighbours_dirty(pos);
+        if previous_chunk.is_some() {
+            self.history.clear();
+        }
+        return previous_chunk;
+    }
+
+    /// Unload the chunk at `pos`, its loaded neighbours are marked dirty
+    ///
+    /// A chunk inserted and removed again before the events are sent is only reported as unloaded.
+    pub fn remove_chunk(&mut self, pos: IVec2) -> Option<Chunk<T>> {
+        let quadrant = self.get_quadrant_mut(pos);
+        let chunk = quadrant.get_mut(pos.x.abs() as usize)?.get_mut(pos.y.abs() as usize)?.take()?;
+        self.loaded_chunks.retain(|loaded_pos| *loaded_pos != pos);
+        self.unloaded_chunks.push(pos);
+        self.dirty_chunks.remove(&pos);
+        self.mark_chunk_and_neighbours_dirty(pos);
+        return Some(chunk);
+    }
+
+    /// Return the coordinates of every loaded chunk
+    pub fn chunk_positions(&self) -> Vec<IVec2> {
+        let signs = [IVec2::new(1, 1), IVec2::new(-1, 1), IVec2::new(-1, -1), IVec2::new(1, -1)];
+        let mut positions = vec![];
+        for (quadrant, sign) in self.quadrants.iter().zip(signs) {
+            for (x, line) in quadrant.iter().enumerate() {
+                for (y, chunk) in line.iter().enumerate() {
+                    let pos = IVec2::new(x as i32, y as i32) * sign;
+                    // Index 0 of a negative quadrant is never reached by `get_chunk`
+                    if chunk.is_some() && Self::chunk_pos_signature(pos) == (sign.x > 0, sign.y > 0) {
+                        positions.push(pos);
+                    }
+                }
+            }
+        }
+        return positions;
+    }
+
     pub fn get_voxel(&self, pos: IVec3) -> Voxel {
         return T::get_voxel_by_id(self.get_voxel_id(pos))
     }
@@ -67,39 +196,519 @@ impl<T: VoxelSet> VoxelWorld<T> {
         }
     }
 
-    pub fn create_chunk_mesh(&self, pos: IVec2) -> Mesh {
-        let mut mesh = Mesh::new(PrimitiveTopology::TriangleList, RenderAssetUsages::default())
-            .with_inserted_attribute(Mesh::ATTRIBUTE_POSITION, Vec::<[f32; 3]>::new())
-            .with_inserted_indices(Indices::U32(vec![]));
-        for x in 0..WIDTH {
-            let x = pos.x * WIDTH as i32 + x as i32;
-            for y in 0..HEIGHT {
-                let y = y as i32;
-                for z in 0..WIDTH {
-                    let z = pos.y * WIDTH as i32 + z as i32;
-                    
-                    let pos = IVec3::new(x, y, z);
-                    if T::is_transparent(self.get_voxel_id(IVec3::new(x + 1, y, z))) {
-                        mesh.merge(self.get_voxel(pos).get_face_mesh(Orientation::North).translated_by(Vec3::new(pos.x.rem_euclid(chunk::WIDTH as i32) as f32, pos.y as f32, pos.z.rem_euclid(chunk::WIDTH as i32) as f32)));
+    /// Set the voxel id at `pos`, returning the previous id or `None` if the voxel isn't in a loaded chunk
+    ///
+    /// The chunk is marked dirty when the id changes, as well as the neighbour chunks if the voxel is on a border
+    pub fn set_voxel_id(&mut self, pos: IVec3, voxel_id: T::Id) -> Option<T::Id> {
+        if pos.y < 0 || pos.y >= chunk::HEIGHT as i32 {
+            return None;
+        }
+        let chunk_pos = IVec2::new(pos.x.div_euclid(chunk::WIDTH as i32), pos.z.div_euclid(chunk::WIDTH as i32));
+        let voxel_pos_in_chunk = UVec3::new(pos.x.rem_euclid(chunk::WIDTH as i32) as u32, pos.y as u32, pos.z.rem_euclid(chunk::WIDTH as i32) as u32);
+        let chunk = self.get_chunk_mut(chunk_pos)?;
+        let old_voxel_id = chunk.get_voxel_id(voxel_pos_in_chunk);
+        if old_voxel_id != voxel_id {
+            chunk.set_voxel_id(voxel_pos_in_chunk, voxel_id);
+            self.voxel_changes.push((pos, old_voxel_id, voxel_id));
+            self.mark_voxel_dirty(pos);
+            self.history.record(chunk_pos, voxel_pos_in_chunk, old_voxel_id, voxel_id);
+        }
+        return Some(old_voxel_id);
+    }
+
+    /// Apply `edit` to the voxels of the loaded chunks between `min` and `max` (inclusive), one chunk at a time
+    ///
+    /// `edit` gets the position and id of a voxel and returns its new id, if any. Return the chunks whose voxels changed
+    pub fn edit_voxels(&mut self, min: IVec3, max: IVec3, mut edit: impl FnMut(IVec3, T::Id) -> Option<T::Id>) -> HashSet<IVec2> {
+        let width = chunk::WIDTH as i32;
+        let min_chunk = IVec2::new(min.x.div_euclid(width), min.z.div_euclid(width));
+        let max_chunk = IVec2::new(max.x.div_euclid(width), max.z.div_euclid(width));
+        let mut modified_chunks = HashSet::new();
+        for chunk_x in min_chunk.x..=max_chunk.x {
+            for chunk_z in min_chunk.y..=max_chunk.y {
+                let chunk_pos = IVec2::new(chunk_x, chunk_z);
+                let origin = IVec3::new(chunk_x * width, 0, chunk_z * width);
+                let from = (min - origin).max(IVec3::ZERO);
+                let to = (max - origin).min(IVec3::new(width - 1, chunk::HEIGHT as i32 - 1, width - 1));
+                let Some(chunk) = self.get_chunk_mut(chunk_pos) else {
+                    continue;
+                };
+                let mut changes = vec![];
+                for x in from.x..=to.x {
+                    for y in from.y..=to.y {
+                        for z in from.z..=to.z {
+                            let voxel_pos_in_chunk = UVec3::new(x as u32, y as u32, z as u32);
+                            let pos = origin + IVec3::new(x, y, z);
+                            let old_voxel_id = chunk.get_voxel_id(voxel_pos_in_chunk);
+                            match edit(pos, old_voxel_id) {
+                                Some(voxel_id) if voxel_id != old_voxel_id => {
+                                    chunk.set_voxel_id(voxel_pos_in_chunk, voxel_id);
+                                    changes.push((pos, old_voxel_id, voxel_id));
+                                }
+                                _ => {}
+                            }
+                        }
                     }
-                    if T::is_transparent(self.get_voxel_id(IVec3::new(x - 1, y, z))) {
-                        mesh.merge(self.get_voxel(pos).get_face_mesh(Orientation::South).translated_by(Vec3::new(pos.x.rem_euclid(chunk::WIDTH as i32) as f32, pos.y as f32, pos.z.rem_euclid(chunk::WIDTH as i32) as f32)));
+                }
+                if !changes.is_empty() {
+                    modified_chunks.insert(chunk_pos);
+                    for (pos, old_voxel_id, voxel_id) in changes.iter() {
+                        let voxel_pos_in_chunk = (*pos - origin).as_uvec3();
+                        self.history.record(chunk_pos, voxel_pos_in_chunk, *old_voxel_id, *voxel_id);
                     }
-                    if T::is_transparent(self.get_voxel_id(IVec3::new(x, y, z + 1))) {
-                        mesh.merge(self.get_voxel(pos).get_face_mesh(Orientation::East).translated_by(Vec3::new(pos.x.rem_euclid(chunk::WIDTH as i32) as f32, pos.y as f32, pos.z.rem_euclid(chunk::WIDTH as i32) as f32)));
+                    self.apply_chunk_changes(chunk_pos, changes);
+                }
+            }
+        }
+        return modified_chunks;
+    }
+
+    /// Mark dirty and send the `(position, old id, new id)` changes made to the voxels of a chunk
+    fn apply_chunk_changes(&mut self, chunk_pos: IVec2, changes: Vec<(IVec3, T::Id, T::Id)>) {
+        let width = chunk::WIDTH as i32;
+        self.mark_chunk_dirty(chunk_pos);
+        for (pos, _, _) in changes.iter() {
+            let (x, z) = (pos.x.rem_euclid(width), pos.z.rem_euclid(width));
+            // Only border voxels can make a neighbour chunk dirty
+            if x == 0 || x == width - 1 || z == 0 || z == width - 1 {
+                self.mark_voxel_dirty(*pos);
+            }
+        }
+        self.voxel_changes.extend(changes);
+    }
+
+    /// Apply the runs of a transaction to a chunk, setting their voxels to the id given with each run
+    fn apply_edit_runs<'a>(&mut self, chunk_pos: IVec2, runs: impl Iterator<Item = (&'a EditRun<T::Id>, T::Id)>)
+    where
+        T::Id: 'a,
+    {
+        let origin = IVec3::new(chunk_pos.x * chunk::WIDTH as i32, 0, chunk_pos.y * chunk::WIDTH as i32);
+        let Some(chunk) = self.get_chunk_mut(chunk_pos) else {
+            return;
+        };
+        let mut changes = vec![];
+        for (run, voxel_id) in runs {
+            for voxel_pos_in_chunk in run.voxels() {
+                let old_voxel_id = chunk.get_voxel_id(voxel_pos_in_chunk);
+                if old_voxel_id != voxel_id {
+                    chunk.set_voxel_id(voxel_pos_in_chunk, voxel_id);
+                    changes.push((origin + voxel_pos_in_chunk.as_ivec3(), old_voxel_id, voxel_id));
+                }
+            }
+        }
+        if !changes.is_empty() {
+            self.apply_chunk_changes(chunk_pos, changes);
+        }
+    }
+
+    /// Start recording the edits in a transaction named `name`, the open transaction is committed first
+    pub fn begin_transaction(&mut self, name: &str) {
+        self.history.begin(name);
+    }
+
+    /// Add the open transaction to the undo history, if it changed anything
+    ///
+    /// Return `false` if it was too large for the history memory limit, its edits are kept but can't be undone and the history is cleared
+    pub fn commit_transaction(&mut self) -> bool {
+        return self.history.commit();
+    }
+
+    /// Record the edits made by `edit` in a transaction named `name`
+    ///
+    /// Use `begin_transaction` and `commit_transaction` to know whether the edits can be undone
+    pub fn transaction<R>(&mut self, name: &str, edit: impl FnOnce(&mut Self) -> R) -> R {
+        self.begin_transaction(name);
+        let result = edit(self);
+        self.commit_transaction();
+        return result;
+    }
+
+    /// Revert the last transaction, returning its name
+    ///
+    /// The open transaction is committed first, voxels of chunks that have been unloaded stay unchanged
+    pub fn undo(&mut self) -> Option<String> {
+        self.history.commit();
+        let transaction = self.history.pop_undo()?;
+        for (chunk_pos, runs) in transaction.chunks.iter() {
+            self.apply_edit_runs(*chunk_pos, runs.iter().rev().map(|run| (run, run.old_id)));
+        }
+        let name = transaction.name.clone();
+        self.history.push_redo(transaction);
+        return Some(name);
+    }
+
+    /// Apply again the last undone transaction, returning its name
+    pub fn redo(&mut self) -> Option<String> {
+        self.history.commit();
+        let transaction = self.history.pop_redo()?;
+        for (chunk_pos, runs) in transaction.chunks.iter() {
+            self.apply_edit_runs(*chunk_pos, runs.iter().map(|run| (run, run.new_id)));
+        }
+        let name = transaction.name.clone();
+        self.history.push_undo(transaction);
+        return Some(name);
+    }
+
+    pub fn history(&self) -> &EditHistory<T::Id> {
+        return &self.history;
+    }
+
+    /// Mark the chunk containing `pos` dirty, and the neighbour chunks whose mesh depends on this voxel
+    pub fn mark_voxel_dirty(&mut self, pos: IVec3) {
+        let chunk_pos = IVec2::new(pos.x.div_euclid(chunk::WIDTH as i32), pos.z.div_euclid(chunk::WIDTH as i32));
+        let border_offset = |coord: i32| -> i32 {
+            match coord.rem_euclid(chunk::WIDTH as i32) {
+                0 => -1,
+                c if c == chunk::WIDTH as i32 - 1 => 1,
+                _ => 0,
+            }
+        };
+        let offset = IVec2::new(border_offset(pos.x), border_offset(pos.z));
+        self.mark_chunk_dirty(chunk_pos);
+        self.mark_chunk_dirty(chunk_pos + IVec2::new(offset.x, 0));
+        self.mark_chunk_dirty(chunk_pos + IVec2::new(0, offset.y));
+        self.mark_chunk_dirty(chunk_pos + offset);
+    }
+
+    fn mark_chunk_and_neighbours_dirty(&mut self, pos: IVec2) {
+        for x in -1..=1 {
+            for y in -1..=1 {
+                self.mark_chunk_dirty(pos + IVec2::new(x, y));
+            }
+        }
+    }
+
+    /// Mark a chunk as needing a new mesh, chunks that aren't loaded are ignored
+    pub fn mark_chunk_dirty(&mut self, pos: IVec2) {
+        if self.get_chunk(pos).is_some() {
+            self.dirty_chunks.insert(pos);
+        }
+    }
+
+    pub fn is_chunk_dirty(&self, pos: IVec2) -> bool {
+        return self.dirty_chunks.contains(&pos);
+    }
+
+    /// Number of chunks waiting to be remeshed
+    pub fn dirty_chunk_count(&self) -> usize {
+        return self.dirty_chunks.len();
+    }
+
+    /// Return the dirty chunks and clear the dirty flags, the caller is expected to remesh them
+    pub fn take_dirty_chunks(&mut self) -> Vec<IVec2> {
+        return self.dirty_chunks.drain().collect();
+    }
+
+    /// Return the chunks loaded since the last call
+    pub fn take_loaded_chunks(&mut self) -> Vec<IVec2> {
+        return std::mem::take(&mut self.loaded_chunks);
+    }
+
+    /// Return the chunks unloaded since the last call
+    pub fn take_unloaded_chunks(&mut self) -> Vec<IVec2> {
+        return std::mem::take(&mut self.unloaded_chunks);
+    }
+
+    /// Return the `(position, old id, new id)` of the voxels changed since the last call
+    pub fn take_voxel_changes(&mut self) -> Vec<(IVec3, T::Id, T::Id)> {
+        return std::mem::take(&mut self.voxel_changes);
+    }
+
+    /// Create the opaque mesh of a chunk with the world meshing mode
+    pub fn create_chunk_mesh(&self, pos: IVec2) -> Mesh {
+        return self.create_chunk_lod_mesh(pos, 0);
+    }
+
+    /// Create the translucent mesh of a chunk (glass, water, ...), it has to be rendered with an alpha blended material
+    pub fn create_chunk_transparent_mesh(&self, pos: IVec2) -> Mesh {
+        return self.create_chunk_pass_mesh(pos, true, 0);
+    }
+
+    /// Create the opaque mesh of a chunk downsampled by `2^lod` on each axis (`lod` 0 is full resolution)
+    ///
+    /// With blocky meshing, faces on the chunk border are kept for `lod > 0` unless the neighbour cell is entirely solid,
+    /// they act as skirts hiding the cracks with neighbour chunks meshed at another level of detail
+    pub fn create_chunk_lod_mesh(&self, pos: IVec2, lod: u32) -> Mesh {
+        match self.meshing_mode {
+            MeshingMode::Blocky => return self.create_chunk_pass_mesh(pos, false, lod),
+            MeshingMode::SurfaceNets => return self.create_chunk_smooth_mesh(pos, lod),
+        }
+    }
+
+    /// Return the voxel id representing the `2^lod` sized cell at `cell` (in cell coordinates)
+    ///
+    /// The cell is solid if at least half of its voxels are, so that thin surfaces are kept,
+    /// the most frequent id of the winning kind is then picked
+    pub fn get_lod_voxel_id(&self, cell: IVec3, lod: u32) -> T::Id {
+        if lod == 0 {
+            return self.get_voxel_id(cell);
+        }
+        let size = 1 << lod;
+        let origin = cell * size;
+        let mut solid_count = 0;
+        let mut solid_ids: Vec<(T::Id, u32)> = vec![];
+        let mut transparent_ids: Vec<(T::Id, u32)> = vec![];
+        for x in 0..size {
+            for y in 0..size {
+                for z in 0..size {
+                    let voxel_id = self.get_voxel_id(origin + IVec3::new(x, y, z));
+                    let ids = if T::is_transparent(voxel_id) {
+                        &mut transparent_ids
+                    } else {
+                        solid_count += 1;
+                        &mut solid_ids
+                    };
+                    match ids.iter_mut().find(|(id, _)| *id == voxel_id) {
+                        Some((_, count)) => *count += 1,
+                        None => ids.push((voxel_id, 1)),
                     }
-                    if T::is_transparent(self.get_voxel_id(IVec3::new(x, y, z - 1))) {
-                        mesh.merge(self.get_voxel(pos).get_face_mesh(Orientation::West).translated_by(Vec3::new(pos.x.rem_euclid(chunk::WIDTH as i32) as f32, pos.y as f32, pos.z.rem_euclid(chunk::WIDTH as i32) as f32)));
+                }
+            }
+        }
+        let ids = if solid_count * 2 >= size * size * size { solid_ids } else { transparent_ids };
+        return ids.into_iter().max_by_key(|(_, count)| *count).map(|(id, _)| id).unwrap_or(T::get_default_voxel_id());
+    }
+
+    /// Check if every voxel of the `2^lod` sized cell at `cell` (in cell coordinates) is solid, whatever the level of detail of its chunk nothing behind it can be seen
+    fn is_lod_cell_solid(&self, cell: IVec3, lod: u32) -> bool {
+        let size = 1 << lod;
+        let origin = cell * size;
+        for x in 0..size {
+            for y in 0..size {
+                for z in 0..size {
+                    if T::is_transparent(self.get_voxel_id(origin + IVec3::new(x, y, z))) {
+                        return false;
                     }
-                    if T::is_transparent(self.get_voxel_id(IVec3::new(x, y + 1, z))) {
-                        mesh.merge(self.get_voxel(pos).get_face_mesh(Orientation::Up).translated_by(Vec3::new(pos.x.rem_euclid(chunk::WIDTH as i32) as f32, pos.y as f32, pos.z.rem_euclid(chunk::WIDTH as i32) as f32)));
+                }
+            }
+        }
+        return true;
+    }
+
+    /// Check if the face of `voxel_id` touching `neighbour_id` has to be meshed
+    ///
+    /// Opaque voxels show their faces against any transparent voxel, translucent voxels only
+    /// against transparent voxels of another type, so that water or glass volumes have no inner faces
+    fn is_face_visible(voxel_id: T::Id, neighbour_id: T::Id) -> bool {
+        if !T::is_transparent(neighbour_id) {
+            return false;
+        }
+        if T::is_transparent(voxel_id) {
+            return voxel_id != neighbour_id;
+        }
+        return true;
+    }
+
+    fn create_chunk_pass_mesh(&self, pos: IVec2, transparent: bool, lod: u32) -> Mesh {
+        let mut mesh = Mesh::new(PrimitiveTopology::TriangleList, RenderAssetUsages::default())
+            .with_inserted_attribute(Mesh::ATTRIBUTE_POSITION, Vec::<[f32; 3]>::new())
+            .with_inserted_indices(Indices::U32(vec![]));
+        let neighbours = [
+            (IVec3::X, Orientation::North),
+            (IVec3::NEG_X, Orientation::South),
+            (IVec3::Z, Orientation::East),
+            (IVec3::NEG_Z, Orientation::West),
+            (IVec3::Y, Orientation::Up),
+            (IVec3::NEG_Y, Orientation::Down),
+        ];
+        let size = 1 << lod;
+        let width = WIDTH as i32 / size;
+        let height = HEIGHT as i32 / size;
+        for x in 0..width {
+            for y in 0..height {
+                for z in 0..width {
+                    let local_pos = IVec3::new(x, y, z);
+                    let pos = IVec3::new(pos.x * width + x, y, pos.y * width + z);
+                    let voxel_id = self.get_lod_voxel_id(pos, lod);
+                    let voxel = T::get_voxel_by_id(voxel_id);
+                    if !voxel.has_faces() || T::is_transparent(voxel_id) != transparent {
+                        continue;
                     }
-                    if T::is_transparent(self.get_voxel_id(IVec3::new(x, y - 1, z))) {
-                        mesh.merge(self.get_voxel(pos).get_face_mesh(Orientation::Down).translated_by(Vec3::new(pos.x.rem_euclid(chunk::WIDTH as i32) as f32, pos.y as f32, pos.z.rem_euclid(chunk::WIDTH as i32) as f32)));
+                    let offset = (local_pos * size).as_vec3();
+                    for (direction, orientation) in neighbours {
+                        let neighbour_local_pos = local_pos + direction;
+                        let is_skirt = lod > 0
+                            && direction.y == 0
+                            && (neighbour_local_pos.x < 0 || neighbour_local_pos.x >= width || neighbour_local_pos.z < 0 || neighbour_local_pos.z >= width)
+                            && !self.is_lod_cell_solid(pos + direction, lod);
+                        if is_skirt || Self::is_face_visible(voxel_id, self.get_lod_voxel_id(pos + direction, lod)) {
+                            mesh.merge(voxel.get_face_mesh(orientation).scaled_by(Vec3::splat(size as f32)).translated_by(offset));
+                        }
                     }
                 }
             }
         }
         return mesh.with_duplicated_vertices().with_computed_flat_normals();
     }
-}
\ No newline at end of file
+}
+
+/// Chunks are stored one after the other, each as its coordinates (two little endian `i32`) followed by its voxel ids in x, y, z order
+impl<T: VoxelSet<Id = u8>> VoxelWorld<T> {
+    /// Write the loaded chunks, returning the chunk count
+    pub fn write_chunks(&self, mut writer: impl Write) -> io::Result<usize> {
+        let positions = self.chunk_positions();
+        for pos in positions.iter() {
+            let Some(chunk) = self.get_chunk(*pos) else {
+                continue;
+            };
+            writer.write_all(&pos.x.to_le_bytes())?;
+            writer.write_all(&pos.y.to_le_bytes())?;
+            for layer in chunk.clone_voxels() {
+                for row in layer {
+                    writer.write_all(&row)?;
+                }
+            }
+        }
+        return Ok(positions.len());
+    }
+
+    /// Create a world holding the chunks written by `write_chunks`
+    pub fn read_chunks(mut reader: impl Read) -> io::Result<Self> {
+        let mut bytes = vec![];
+        reader.read_to_end(&mut bytes)?;
+        let chunk_size = 8 + WIDTH * HEIGHT * WIDTH;
+        if bytes.len() % chunk_size != 0 {
+            return Err(io::Error::new(io::ErrorKind::InvalidData, "truncated world file"));
+        }
+        let mut world = Self::new(Default::default());
+        for chunk_bytes in bytes.chunks_exact(chunk_size) {
+            let x = i32::from_le_bytes([chunk_bytes[0], chunk_bytes[1], chunk_bytes[2], chunk_bytes[3]]);
+            let z = i32::from_le_bytes([chunk_bytes[4], chunk_bytes[5], chunk_bytes[6], chunk_bytes[7]]);
+            let mut voxels = [[[0; WIDTH]; HEIGHT]; WIDTH];
+            for (i, voxel_id) in chunk_bytes[8..].iter().enumerate() {
+                voxels[i / (HEIGHT * WIDTH)][i / WIDTH % HEIGHT][i % WIDTH] = *voxel_id;
+            }
+            world.insert_chunk(IVec2::new(x, z), Chunk::new(voxels));
+        }
+        return Ok(world);
+    }
+
+    /// Write the loaded chunks to the file at `path`, returning the chunk count
+    pub fn save_chunks(&self, path: impl AsRef<Path>) -> io::Result<usize> {
+        let mut writer = BufWriter::new(File::create(path)?);
+        let count = self.write_chunks(&mut writer)?;
+        writer.flush()?;
+        return Ok(count);
+    }
+
+    /// Create a world holding the chunks of the file at `path`
+    pub fn load_chunks(path: impl AsRef<Path>) -> io::Result<Self> {
+        return Self::read_chunks(BufReader::new(File::open(path)?));
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use bevy::{math::{IVec2, IVec3}, render::mesh::Mesh};
+
+    use crate::{
+        chunk::{Chunk, HEIGHT, WIDTH},
+        test_util::{empty_world, filled_chunk, TestSet},
+        voxel::BasicSet,
+    };
+
+    use super::VoxelWorld;
+
+    /// Number of quads of a mesh, its vertices are duplicated
+    fn face_count(mesh: &Mesh) -> usize {
+        return mesh.count_vertices() / 6;
+    }
+
+    type World = VoxelWorld<BasicSet>;
+
+    #[test]
+    fn culls_faces_between_opaque_and_transparent_voxels() {
+        let (air, grass, glass, water) = (0, 1, 2, 3);
+        // Opaque voxels show their faces against any transparent voxel
+        assert!(World::is_face_visible(grass, air));
+        assert!(World::is_face_visible(grass, glass));
+        assert!(World::is_face_visible(grass, water));
+        assert!(!World::is_face_visible(grass, grass));
+        // Translucent voxels only against transparent voxels of another type
+        assert!(World::is_face_visible(water, air));
+        assert!(World::is_face_visible(glass, water));
+        assert!(!World::is_face_visible(glass, glass));
+        assert!(!World::is_face_visible(water, water));
+        assert!(!World::is_face_visible(glass, grass));
+
+        // Two glass voxels side by side, one of them on a grass voxel whose top face stays visible through the glass
+        let mut world = World::new(Default::default());
+        world.insert_chunk(IVec2::ZERO, Chunk::new([[[air; WIDTH]; HEIGHT]; WIDTH]));
+        world.set_voxel_id(IVec3::new(4, 4, 4), grass);
+        world.set_voxel_id(IVec3::new(4, 5, 4), glass);
+        world.set_voxel_id(IVec3::new(5, 5, 4), glass);
+        assert_eq!(face_count(&world.create_chunk_mesh(IVec2::ZERO)), 6);
+        assert_eq!(face_count(&world.create_chunk_transparent_mesh(IVec2::ZERO)), 2 * 6 - 2 - 1);
+    }
+
+    #[test]
+    fn downsamples_lod_cells() {
+        let mut world = empty_world([IVec2::ZERO]);
+        // Half of the cell is solid, the most frequent solid id wins
+        for (pos, voxel_id) in [(IVec3::new(0, 0, 0), 1), (IVec3::new(1, 0, 0), 1), (IVec3::new(0, 1, 0), 1), (IVec3::new(1, 1, 1), 2)] {
+            world.set_voxel_id(pos, voxel_id);
+        }
+        assert_eq!(world.get_lod_voxel_id(IVec3::ZERO, 1), 1);
+        assert_eq!(world.get_lod_voxel_id(IVec3::ZERO, 0), 1);
+        assert_eq!(world.get_lod_voxel_id(IVec3::new(1, 1, 1), 0), 2);
+        // At the next level the same voxels are an eighth of the cell
+        assert_eq!(world.get_lod_voxel_id(IVec3::ZERO, 2), 0);
+
+        let mut world = World::new(Default::default());
+        world.insert_chunk(IVec2::ZERO, Chunk::new([[[0; WIDTH]; HEIGHT]; WIDTH]));
+        // 3 water, 1 glass, 3 grass and 1 air voxels : less than half solid, water is the most frequent transparent id
+        let ids = [3, 3, 3, 2, 1, 1, 1, 0];
+        for (i, voxel_id) in ids.into_iter().enumerate() {
+            world.set_voxel_id(IVec3::new(i as i32 & 1, (i as i32 >> 1) & 1, (i as i32 >> 2) & 1), voxel_id);
+        }
+        assert_eq!(world.get_lod_voxel_id(IVec3::ZERO, 1), 3);
+    }
+
+    #[test]
+    fn only_adds_skirts_against_non_solid_cells() {
+        // Flat ground 8 voxels deep over two chunks, 4 cells deep at level 1
+        let mut world = empty_world([IVec2::ZERO, IVec2::X]);
+        world.edit_voxels(IVec3::ZERO, IVec3::new(2 * WIDTH as i32 - 1, 7, WIDTH as i32 - 1), |_, _| Some(1));
+        let cells = WIDTH / 2;
+        // Top, bottom and the faces against the three unloaded neighbours, the ground continues in the loaded one
+        assert_eq!(face_count(&world.create_chunk_lod_mesh(IVec2::ZERO, 1)), 2 * cells * cells + 3 * cells * 4);
+        assert_eq!(face_count(&world.create_chunk_lod_mesh(IVec2::ZERO, 0)), 2 * WIDTH * WIDTH + 3 * WIDTH * 8);
+
+        // A hole the level 1 neighbour cell hides is still visible when that chunk is at full resolution
+        world.set_voxel_id(IVec3::new(WIDTH as i32, 7, 5), 0);
+        assert_eq!(world.get_lod_voxel_id(IVec3::new(cells as i32, 3, 2), 1), 1);
+        assert_eq!(face_count(&world.create_chunk_lod_mesh(IVec2::ZERO, 1)), 2 * cells * cells + 3 * cells * 4 + 1);
+    }
+
+    #[test]
+    fn round_trips_chunk_files() {
+        let mut world = empty_world([IVec2::new(-2, 3), IVec2::ZERO]);
+        world.set_voxel_id(IVec3::new(-30, 127, 60), 3);
+        world.set_voxel_id(IVec3::new(1, 2, 3), 1);
+        let mut bytes = vec![];
+        assert_eq!(world.write_chunks(&mut bytes).unwrap(), 2);
+
+        let loaded = VoxelWorld::<TestSet>::read_chunks(bytes.as_slice()).unwrap();
+        let mut positions = loaded.chunk_positions();
+        positions.sort_by_key(|pos| (pos.x, pos.y));
+        assert_eq!(positions, vec![IVec2::new(-2, 3), IVec2::ZERO]);
+        assert_eq!(loaded.get_voxel_id(IVec3::new(-30, 127, 60)), 3);
+        assert_eq!(loaded.get_voxel_id(IVec3::new(1, 2, 3)), 1);
+        assert!(VoxelWorld::<TestSet>::read_chunks(&bytes[1..]).is_err());
+    }
+
+    #[test]
+    fn reports_the_last_operation_on_a_chunk() {
+        let mut world = empty_world([IVec2::ZERO]);
+        world.take_loaded_chunks();
+        assert!(world.remove_chunk(IVec2::ZERO).is_some());
+        world.insert_chunk(IVec2::ZERO, filled_chunk(1));
+        assert_eq!(world.take_loaded_chunks(), vec![IVec2::ZERO]);
+        assert!(world.take_unloaded_chunks().is_empty());
+
+        world.insert_chunk(IVec2::X, filled_chunk(1));
+        world.remove_chunk(IVec2::X);
+        assert!(world.take_loaded_chunks().is_empty());
+        assert_eq!(world.take_unloaded_chunks(), vec![IVec2::X]);
+    }
+}
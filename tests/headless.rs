@@ -0,0 +1,434 @@
+use bevy::{
+    app::{App, AppExit},
+    asset::{AssetPlugin, Assets, Handle},
+    ecs::{event::Events, system::RunSystemOnce},
+    input::{
+        gamepad::{Gamepad, GamepadAxis, GamepadAxisType},
+        keyboard::KeyCode,
+        mouse::MouseButton,
+        Axis, ButtonInput,
+    },
+    math::{IVec2, IVec3, UVec3},
+    render::mesh::Mesh,
+    MinimalPlugins,
+};
+#[cfg(feature = "render")]
+use bevy::{
+    ecs::{entity::Entity, schedule::State, world::Mut},
+    input::mouse::{MouseMotion, MouseWheel},
+    math::{Vec2, Vec3},
+    time::TimeUpdateStrategy,
+    transform::components::Transform,
+    window::{ReceivedCharacter, WindowFocused},
+};
+#[cfg(feature = "render")]
+use std::time::Duration;
+use voxel_engine::{meshing::remesh_dirty_chunks, prelude::*};
+#[cfg(feature = "render")]
+use voxel_engine::camera_path::CameraKeyframe;
+
+struct TestSet;
+
+impl VoxelSet for TestSet {
+    type Id = u8;
+
+    fn get_voxel_by_id(voxel_id: Self::Id) -> Voxel {
+        match voxel_id {
+            0 => Voxel::Air,
+            1 => Voxel::Grass,
+            2 => Voxel::Glass,
+            _ => Voxel::Error,
+        }
+    }
+
+    fn is_transparent(voxel_id: Self::Id) -> bool {
+        voxel_id == 0 || voxel_id == 2
+    }
+
+    fn get_default_voxel_id() -> Self::Id {
+        0
+    }
+}
+
+fn headless_app() -> App {
+    let mut app = App::new();
+    app.add_plugins((MinimalPlugins, AssetPlugin::default(), VoxelWorldPlugin::<TestSet>::new()));
+    // Done by `App::run`, needed here since the app is ticked by hand
+    app.finish();
+    app.cleanup();
+    return app;
+}
+
+/// Chunk with a `height` voxels high layer of grass
+fn flat_chunk(height: usize) -> Chunk<TestSet> {
+    let mut voxels = [[[0; WIDTH]; HEIGHT]; WIDTH];
+    for column in voxels.iter_mut() {
+        for row in column.iter_mut().take(height) {
+            row.fill(1);
+        }
+    }
+    return Chunk::new(voxels);
+}
+
+fn chunk_vertex_count(app: &mut App, pos: IVec2) -> usize {
+    let mut q_chunks = app.world.query::<(&ChunkMarker, &Handle<Mesh>)>();
+    let meshes = app.world.resource::<Assets<Mesh>>();
+    let (_, handle) = q_chunks.iter(&app.world).find(|(marker, _)| marker.pos == pos).expect("chunk entity not spawned");
+    return meshes.get(handle).expect("chunk mesh not generated").count_vertices();
+}
+
+#[test]
+fn generates_and_meshes_chunks_without_renderer() {
+    let mut app = headless_app();
+    {
+        let mut world = app.world.resource_mut::<VoxelWorld<TestSet>>();
+        world.insert_chunk(IVec2::new(0, 0), flat_chunk(1));
+        world.insert_chunk(IVec2::new(1, 0), flat_chunk(1));
+    }
+    app.update();
+
+    let world = app.world.resource::<VoxelWorld<TestSet>>();
+    assert_eq!(world.get_chunk(IVec2::new(0, 0)).unwrap().get_voxel_id(UVec3::new(3, 0, 7)), 1);
+    assert_eq!(world.get_voxel_id(IVec3::new(20, 1, 3)), 0);
+    assert!(world.get_chunk(IVec2::new(2, 0)).is_none());
+
+    // Top and bottom faces of 16x16 voxels, three open sides of 16 faces, 6 vertices per face
+    assert_eq!(chunk_vertex_count(&mut app, IVec2::new(0, 0)), (2 * WIDTH * WIDTH + 3 * WIDTH) * 6);
+    assert_eq!(chunk_vertex_count(&mut app, IVec2::new(1, 0)), (2 * WIDTH * WIDTH + 3 * WIDTH) * 6);
+    assert_eq!(app.world.resource::<Events<ChunkLoaded>>().len(), 2);
+    assert_eq!(app.world.resource::<Events<ChunkMeshed>>().len(), 2);
+}
+
+#[test]
+fn remeshes_edited_chunks() {
+    let mut app = headless_app();
+    app.world.resource_mut::<VoxelWorld<TestSet>>().insert_chunk(IVec2::new(0, 0), flat_chunk(1));
+    app.update();
+    let vertex_count = chunk_vertex_count(&mut app, IVec2::new(0, 0));
+
+    // A voxel placed on the ground adds its top and four side faces and hides one ground face
+    let old = app.world.resource_mut::<VoxelWorld<TestSet>>().set_voxel_id(IVec3::new(5, 1, 5), 1);
+    assert_eq!(old, Some(0));
+    app.update();
+    assert_eq!(chunk_vertex_count(&mut app, IVec2::new(0, 0)), vertex_count + 4 * 6);
+
+    let voxel_changed_events = app.world.resource::<Events<VoxelChanged<TestSet>>>();
+    let event = voxel_changed_events.iter_current_update_events().next().unwrap();
+    assert_eq!((event.pos, event.old, event.new), (IVec3::new(5, 1, 5), 0, 1));
+}
+
+#[test]
+fn keeps_the_entity_of_chunks_reloaded_in_one_frame() {
+    let mut app = headless_app();
+    app.world.resource_mut::<VoxelWorld<TestSet>>().insert_chunk(IVec2::new(0, 0), flat_chunk(1));
+    app.update();
+
+    let mut world = app.world.resource_mut::<VoxelWorld<TestSet>>();
+    world.remove_chunk(IVec2::new(0, 0));
+    world.insert_chunk(IVec2::new(0, 0), flat_chunk(2));
+    app.update();
+    app.update();
+    assert_eq!(app.world.query::<&ChunkMarker>().iter(&app.world).count(), 1);
+    assert_eq!(chunk_vertex_count(&mut app, IVec2::new(0, 0)), (2 * WIDTH * WIDTH + 4 * 2 * WIDTH) * 6);
+}
+
+/// Levels of detail of the chunk entity whose mesh was generated
+fn generated_lod_levels(app: &mut App) -> Vec<usize> {
+    let mut q_chunks = app.world.query::<(&ChunkLodMeshes, &Handle<Mesh>)>();
+    let (lod_meshes, mesh) = q_chunks.single(&app.world);
+    assert_eq!(lod_meshes.meshes[lod_meshes.level].as_ref(), Some(mesh));
+    return (0..lod_meshes.meshes.len()).filter(|level| lod_meshes.meshes[*level].is_some()).collect();
+}
+
+#[test]
+fn generates_only_the_level_of_detail_in_use() {
+    let mut app = headless_app();
+    app.world.resource_mut::<VoxelWorld<TestSet>>().insert_chunk(IVec2::new(0, 0), flat_chunk(4));
+    app.update();
+    assert_eq!(generated_lod_levels(&mut app), vec![0]);
+
+    app.world.query::<&mut ChunkLodMeshes>().single_mut(&mut app.world).level = 2;
+    app.update();
+    assert_eq!(generated_lod_levels(&mut app), vec![0, 2]);
+
+    // An edit only regenerates the level in use
+    app.world.resource_mut::<VoxelWorld<TestSet>>().set_voxel_id(IVec3::new(5, 4, 5), 1);
+    app.update();
+    assert_eq!(generated_lod_levels(&mut app), vec![2]);
+}
+
+#[test]
+fn despawns_unloaded_chunks() {
+    let mut app = headless_app();
+    app.world.resource_mut::<VoxelWorld<TestSet>>().insert_chunk(IVec2::new(-1, 2), flat_chunk(4));
+    app.update();
+    assert_eq!(app.world.query::<&ChunkMarker>().iter(&app.world).count(), 1);
+
+    assert!(app.world.resource_mut::<VoxelWorld<TestSet>>().remove_chunk(IVec2::new(-1, 2)).is_some());
+    app.update();
+    app.update();
+    assert_eq!(app.world.query::<&ChunkMarker>().iter(&app.world).count(), 0);
+    assert!(app.world.resource::<Events<AppExit>>().is_empty());
+}
+
+#[test]
+fn keeps_chunks_dirty_until_their_entity_is_spawned() {
+    let mut app = headless_app();
+    app.world.resource_mut::<VoxelWorld<TestSet>>().insert_chunk(IVec2::ZERO, flat_chunk(4));
+    app.world.run_system_once(remesh_dirty_chunks::<TestSet>);
+    assert!(app.world.resource::<VoxelWorld<TestSet>>().is_chunk_dirty(IVec2::ZERO));
+
+    app.update();
+    assert!(!app.world.resource::<VoxelWorld<TestSet>>().is_chunk_dirty(IVec2::ZERO));
+    assert!(chunk_vertex_count(&mut app, IVec2::ZERO) > 0);
+}
+
+#[test]
+fn triggers_actions_from_injected_inputs() {
+    let mut app = App::new();
+    let action_map = ActionMap::default().with_binding(Action::Jump, Binding::Mouse(MouseButton::Middle));
+    app.add_plugins((MinimalPlugins, ActionPlugin::new().with_action_map(action_map)))
+        .init_resource::<ButtonInput<KeyCode>>()
+        .init_resource::<ButtonInput<MouseButton>>()
+        .init_resource::<Axis<GamepadAxis>>();
+    app.update();
+    assert!(!app.world.resource::<ActionState>().pressed(Action::MoveForward));
+
+    app.world.resource_mut::<ButtonInput<KeyCode>>().press(KeyCode::KeyW);
+    app.update();
+    let actions = app.world.resource::<ActionState>();
+    assert!(actions.pressed(Action::MoveForward) && actions.just_pressed(Action::MoveForward));
+    assert!(!actions.pressed(Action::MoveBackward));
+
+    // Both bindings of the same action, releasing one keeps it pressed
+    app.world.resource_mut::<ButtonInput<MouseButton>>().press(MouseButton::Middle);
+    app.world.resource_mut::<ButtonInput<KeyCode>>().press(KeyCode::Space);
+    app.update();
+    app.world.resource_mut::<ButtonInput<KeyCode>>().release(KeyCode::Space);
+    app.update();
+    let actions = app.world.resource::<ActionState>();
+    assert!(actions.pressed(Action::Jump) && !actions.just_pressed(Action::Jump));
+    assert!(actions.pressed(Action::MoveForward) && !actions.just_pressed(Action::MoveForward));
+
+    app.world.resource_mut::<ButtonInput<KeyCode>>().release(KeyCode::KeyW);
+    app.world.resource_mut::<Axis<GamepadAxis>>().set(GamepadAxis::new(Gamepad::new(0), GamepadAxisType::LeftStickX), -0.8);
+    app.update();
+    let actions = app.world.resource::<ActionState>();
+    assert!(actions.just_released(Action::MoveForward));
+    assert!(actions.pressed(Action::MoveLeft) && !actions.pressed(Action::MoveRight));
+}
+
+/// Advance a frame, then clear the just pressed inputs like the input plugin does
+#[cfg(feature = "render")]
+fn update_with_keys(app: &mut App) {
+    app.update();
+    app.world.resource_mut::<ButtonInput<KeyCode>>().clear();
+}
+
+#[cfg(feature = "render")]
+#[test]
+fn keeps_console_closed_while_closing_key_is_held() {
+    let mut app = App::new();
+    app.add_plugins((MinimalPlugins, ActionPlugin::new(), ConsolePlugin::<TestSet>::new()))
+        .add_event::<ReceivedCharacter>()
+        .init_resource::<ButtonInput<KeyCode>>();
+    update_with_keys(&mut app);
+
+    for closing_key in [KeyCode::Backquote, KeyCode::Escape] {
+        app.world.resource_mut::<ButtonInput<KeyCode>>().press(KeyCode::Backquote);
+        update_with_keys(&mut app);
+        app.world.resource_mut::<ButtonInput<KeyCode>>().release(KeyCode::Backquote);
+        update_with_keys(&mut app);
+        assert!(app.world.resource::<ConsoleState>().open);
+        assert!(app.world.resource::<ActionState>().captured);
+
+        app.world.resource_mut::<ButtonInput<KeyCode>>().press(closing_key);
+        update_with_keys(&mut app);
+        assert!(!app.world.resource::<ConsoleState>().open);
+
+        // The closing key is still held on the next frames
+        update_with_keys(&mut app);
+        update_with_keys(&mut app);
+        let actions = app.world.resource::<ActionState>();
+        assert!(!app.world.resource::<ConsoleState>().open);
+        assert!(!actions.captured);
+        assert!(!actions.just_pressed(Action::ToggleConsole) && !actions.just_pressed(Action::ReleaseCursor));
+        assert!(actions.pressed(if closing_key == KeyCode::Escape { Action::ReleaseCursor } else { Action::ToggleConsole }));
+
+        app.world.resource_mut::<ButtonInput<KeyCode>>().release(closing_key);
+        update_with_keys(&mut app);
+    }
+}
+
+#[cfg(feature = "render")]
+#[test]
+fn toggles_console_with_rebound_key() {
+    let mut action_map = ActionMap::default();
+    action_map.unbind(Action::ToggleConsole);
+    action_map.bind(Action::ToggleConsole, Binding::Key(KeyCode::F1));
+    let mut app = App::new();
+    app.add_plugins((MinimalPlugins, ActionPlugin::new().with_action_map(action_map), ConsolePlugin::<TestSet>::new()))
+        .add_event::<ReceivedCharacter>()
+        .init_resource::<ButtonInput<KeyCode>>();
+    update_with_keys(&mut app);
+
+    app.world.resource_mut::<ButtonInput<KeyCode>>().press(KeyCode::F1);
+    update_with_keys(&mut app);
+    app.world.resource_mut::<ButtonInput<KeyCode>>().release(KeyCode::F1);
+    update_with_keys(&mut app);
+    assert!(app.world.resource::<ConsoleState>().open);
+
+    // The backquote is an ordinary key once the console is bound to another one
+    app.world.resource_mut::<ButtonInput<KeyCode>>().press(KeyCode::Backquote);
+    app.world.send_event(ReceivedCharacter { window: Entity::PLACEHOLDER, char: "`".into() });
+    update_with_keys(&mut app);
+    assert!(app.world.resource::<ConsoleState>().open);
+    assert_eq!(app.world.resource::<ConsoleState>().input, "`");
+
+    app.world.resource_mut::<ButtonInput<KeyCode>>().press(KeyCode::F1);
+    update_with_keys(&mut app);
+    assert!(!app.world.resource::<ConsoleState>().open);
+}
+
+#[cfg(feature = "render")]
+#[test]
+fn runs_world_commands_from_the_console() {
+    let save_file = std::env::temp_dir().join("voxel_engine_headless_world.bin");
+    let mut app = App::new();
+    app.add_plugins((MinimalPlugins, AssetPlugin::default(), VoxelWorldPlugin::<TestSet>::new(), ActionPlugin::new()))
+        .add_plugins(ConsolePlugin::<TestSet>::new().with_seed(3).with_save_file(&save_file))
+        .add_event::<ReceivedCharacter>();
+    app.finish();
+    app.cleanup();
+    let execute = |app: &mut App, line: &str| -> Vec<String> {
+        return app.world.resource_scope(|world, commands: Mut<ConsoleCommands>| commands.execute(line, world));
+    };
+
+    assert_eq!(execute(&mut app, "seed"), vec!["Seed : 3"]);
+    assert!(execute(&mut app, "fill 0 0 0 100000 127 100000 1")[0].starts_with("error: the region holds"));
+    assert_eq!(execute(&mut app, "regen chunk 1 0")[0], "error: no chunk generator in this app");
+    app.insert_resource(ChunkGenerator::<TestSet>(Box::new(|seed, _| flat_chunk(seed as usize))));
+    assert_eq!(execute(&mut app, "regen chunk 1 0"), vec!["Chunk 1 0 regenerated"]);
+    assert_eq!(app.world.resource::<VoxelWorld<TestSet>>().get_voxel_id(IVec3::new(WIDTH as i32, 2, 0)), 1);
+
+    assert_eq!(execute(&mut app, "save"), vec![format!("1 chunks saved to {}", save_file.display())]);
+    let world = VoxelWorld::<TestSet>::load_chunks(&save_file).unwrap();
+    assert_eq!(world.chunk_positions(), vec![IVec2::X]);
+}
+
+#[cfg(feature = "render")]
+fn camera_translation(app: &mut App, id: u32) -> Vec3 {
+    let mut q_cameras = app.world.query::<(&CameraId, &Transform)>();
+    let (_, transform) = q_cameras.iter(&app.world).find(|(camera_id, _)| camera_id.0 == id).expect("camera not spawned");
+    return transform.translation;
+}
+
+#[cfg(feature = "render")]
+#[test]
+fn moves_the_controlled_camera_only() {
+    let mut app = App::new();
+    app.add_plugins((MinimalPlugins, AssetPlugin::default(), VoxelWorldPlugin::<TestSet>::new(), ActionPlugin::new()))
+        .add_plugins((CameraPlugin::new(0.0, 10.0, 0.0, 0), CameraPlugin::new(50.0, 10.0, 0.0, 1)))
+        .add_plugins((WalkPlugin::<TestSet>::new(0), WalkPlugin::<TestSet>::new(1)))
+        .add_event::<MouseMotion>()
+        .add_event::<MouseWheel>()
+        .add_event::<WindowFocused>()
+        .init_resource::<ButtonInput<KeyCode>>()
+        .init_resource::<ButtonInput<MouseButton>>()
+        .insert_resource(TimeUpdateStrategy::ManualDuration(Duration::from_millis(100)));
+    app.finish();
+    app.cleanup();
+    app.update();
+    assert_eq!(*app.world.resource::<ControlledCamera>(), ControlledCamera(0));
+    assert_eq!(app.world.query::<&WalkController>().iter(&app.world).count(), 2);
+
+    app.world.resource_mut::<ButtonInput<KeyCode>>().press(KeyCode::KeyW);
+    app.update();
+    app.update();
+    assert!(camera_translation(&mut app, 0).z < 0.0);
+    assert_eq!(camera_translation(&mut app, 1), Vec3::new(50.0, 10.0, 0.0));
+
+    app.insert_resource(ControlledCamera(1));
+    let first_camera = camera_translation(&mut app, 0);
+    app.update();
+    assert_eq!(camera_translation(&mut app, 0), first_camera);
+    // Walking systems are shared, the second camera moves at the same speed as the first one did
+    assert!((camera_translation(&mut app, 1).z * 2.0 - first_camera.z).abs() < 1e-4);
+}
+
+#[cfg(feature = "render")]
+#[test]
+fn benchmarks_the_loaded_chunks_of_every_frame() {
+    let benchmark_file = std::env::temp_dir().join("voxel_engine_headless_benchmark.csv");
+    let mut app = App::new();
+    app.add_plugins((MinimalPlugins, AssetPlugin::default(), VoxelWorldPlugin::<TestSet>::new(), ActionPlugin::new()))
+        .add_plugins(CameraPathPlugin::<TestSet>::new(0).with_benchmark_file(&benchmark_file))
+        .init_resource::<ButtonInput<KeyCode>>()
+        .init_resource::<ButtonInput<MouseButton>>();
+    app.finish();
+    app.cleanup();
+    app.world.spawn((CameraId(0), Transform::default()));
+    let mut world = app.world.resource_mut::<VoxelWorld<TestSet>>();
+    world.insert_chunk(IVec2::ZERO, flat_chunk(4));
+    world.insert_chunk(IVec2::X, flat_chunk(4));
+    let keyframes = [0.0, 0.03].map(|time| CameraKeyframe { time: time, translation: Vec3::new(time, 10.0, 0.0), rotation: Default::default() });
+    app.insert_resource(CameraPathState::benchmark(CameraPath { keyframes: keyframes.to_vec() }));
+
+    for _ in 0..10 {
+        app.update();
+        if let CameraPathState::Idle = app.world.resource::<CameraPathState>() {
+            break;
+        }
+    }
+    let benchmark = std::fs::read_to_string(&benchmark_file).unwrap();
+    let rows: Vec<&str> = benchmark.lines().skip(1).collect();
+    // 0, 1/60 and 2/60 seconds along the path
+    assert_eq!(rows.len(), 3);
+    for row in rows {
+        assert_eq!(row.split(',').nth(3), Some("2"));
+    }
+}
+
+#[cfg(feature = "render")]
+#[test]
+fn ignores_the_mouse_while_actions_are_captured() {
+    let mut app = App::new();
+    app.add_plugins((MinimalPlugins, ActionPlugin::new(), CameraPlugin::new(0.0, 10.0, 0.0, 0)))
+        .add_event::<MouseMotion>()
+        .add_event::<MouseWheel>()
+        .add_event::<WindowFocused>()
+        .init_resource::<ButtonInput<KeyCode>>()
+        .init_resource::<ButtonInput<MouseButton>>();
+    app.update();
+    app.world.resource_mut::<ActionState>().captured = true;
+
+    app.world.resource_mut::<ButtonInput<MouseButton>>().press(MouseButton::Left);
+    app.update();
+    app.update();
+    assert_eq!(*app.world.resource::<State<CursorState>>().get(), CursorState::Released);
+
+    // Clicking again once the capture ends grabs the cursor for mouse look
+    app.world.resource_mut::<ActionState>().captured = false;
+    app.world.resource_mut::<ButtonInput<MouseButton>>().release(MouseButton::Left);
+    app.update();
+    app.world.resource_mut::<ButtonInput<MouseButton>>().clear();
+    app.world.resource_mut::<ButtonInput<MouseButton>>().press(MouseButton::Left);
+    app.update();
+    app.update();
+    assert_eq!(*app.world.resource::<State<CursorState>>().get(), CursorState::Grabbed);
+
+    let camera_rotation = |app: &mut App| {
+        let mut q_cameras = app.world.query::<(&CameraId, &Transform)>();
+        return q_cameras.single(&app.world).1.rotation;
+    };
+    let rotation = camera_rotation(&mut app);
+    app.world.resource_mut::<ActionState>().captured = true;
+    app.world.send_event(MouseMotion { delta: Vec2::new(50.0, 20.0) });
+    app.update();
+    assert_eq!(camera_rotation(&mut app), rotation);
+
+    app.world.resource_mut::<ActionState>().captured = false;
+    app.world.send_event(MouseMotion { delta: Vec2::new(50.0, 20.0) });
+    app.update();
+    assert_ne!(camera_rotation(&mut app), rotation);
+}